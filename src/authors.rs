@@ -0,0 +1,123 @@
+use errors::{OResult, OpaqueError};
+use inflector::cases::kebabcase::to_kebab_case;
+use std::collections::BTreeMap;
+use std::path::Path;
+use toml;
+use util::read_file;
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct AuthorLink {
+    pub label: String,
+    pub url: String,
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct Author {
+    /// The author's key in the authors file, filled in after loading
+    #[serde(skip)]
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub bio: String,
+    pub avatar: Option<String>,
+    #[serde(default)]
+    pub links: Vec<AuthorLink>,
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct Authors {
+    /// Author of any post that doesn't name one, the first author if unset
+    default: Option<String>,
+    authors: BTreeMap<String, Author>,
+}
+
+impl Authors {
+    /// The one author of a site without an authors file
+    pub fn single(name: &str, bio: &str) -> Self {
+        let mut id = to_kebab_case(name);
+        if id.is_empty() {
+            id = "author".to_owned();
+        }
+        let author = Author {
+            id: id.clone(),
+            name: name.to_owned(),
+            bio: bio.to_owned(),
+            avatar: None,
+            links: vec![],
+        };
+        let mut authors = BTreeMap::new();
+        authors.insert(id.clone(), author);
+        Authors {
+            default: Some(id),
+            authors,
+        }
+    }
+
+    pub fn from_file<P: AsRef<Path>>(authors_file: P) -> OResult<Self> {
+        Authors::parse(&read_file(authors_file)?)
+    }
+
+    fn parse(contents: &str) -> OResult<Self> {
+        let mut authors: Authors = toml::from_str(contents)?;
+        for (id, author) in authors.authors.iter_mut() {
+            // ids name the author's page, so they can't reach outside the authors directory
+            let is_slug = id
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '-' || c == '_');
+            if id.is_empty() || !is_slug {
+                return Err(OpaqueError::new(format!(
+                    "Author id {:?} can only have letters, numbers, dashes and underscores",
+                    id
+                )));
+            }
+            author.id = id.clone();
+        }
+        if authors.authors.is_empty() {
+            return Err(OpaqueError::new("The authors file has no authors"));
+        }
+        authors.get(None)?;
+        Ok(authors)
+    }
+
+    /// Looks up an author by id, falling back to the default author
+    pub fn get(&self, id: Option<&str>) -> OResult<&Author> {
+        let id = id
+            .or_else(|| self.default.as_ref().map(|s| s.as_str()))
+            .or_else(|| self.authors.keys().next().map(|s| s.as_str()))
+            .unwrap_or("");
+        self.authors
+            .get(id)
+            .ok_or_else(|| OpaqueError::new(format!("Unknown author {}", id)))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Author> {
+        self.authors.values()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_authors() {
+        let authors = Authors::parse(
+            "default = \"sam\"\n\
+             [authors.sam]\n\
+             name = \"Sam\"\n\
+             [authors.alex_2]\n\
+             name = \"Alex\"\n",
+        ).unwrap();
+        assert_eq!(authors.get(None).unwrap().name, "Sam");
+        assert_eq!(authors.get(Some("alex_2")).unwrap().id, "alex_2");
+        assert!(authors.get(Some("nobody")).is_err());
+    }
+
+    #[test]
+    fn rejects_path_ids() {
+        for id in &["\"../x\"", "\"a/b\"", "\"\"", "\"a b\""] {
+            let contents = format!("[authors.{}]\nname = \"Sam\"\n", id);
+            assert!(Authors::parse(&contents).is_err(), "{} is rejected", id);
+        }
+    }
+}
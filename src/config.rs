@@ -2,47 +2,269 @@ extern crate chrono;
 extern crate toml;
 
 use chrono::{DateTime, Utc};
-use errors::OResult;
-use std::collections::BTreeMap;
+use errors::{OResult, OpaqueError};
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs::Metadata;
+use std::mem;
 use std::path::Path;
-use util::{read_file, write_file};
+use util::{content_hash, read_file, write_file};
+
+/// Layout version of the metadata file written by this build
+const METADATA_VERSION: i64 = 3;
 
 #[derive(Debug, Deserialize, Serialize, PartialEq, Clone, PartialOrd)]
 pub struct Timing {
     pub created: DateTime<Utc>,
     pub modified: DateTime<Utc>,
+    /// Hash of the file contents, empty in metadata written before hashes were kept
+    #[serde(default)]
+    pub hash: String,
+    /// Whether both dates come from the commit history, so git needn't be asked again until
+    /// the contents change
+    #[serde(default)]
+    pub from_git: bool,
 }
 
 impl Timing {
-    pub fn from_metadata_and_prev(metadata: &Metadata, prev: Option<&Timing>)
+    /// Only a change in contents counts as a modification, so a checkout, touch or copy keeps
+    /// the previous timing. The first created date recorded for a file is never replaced.
+    pub fn from_contents_and_prev(metadata: &Metadata, contents: &[u8], prev: Option<&Timing>)
         -> OResult<Self>
     {
+        let hash = content_hash(contents);
+        if let Some(prev) = prev.filter(|timing| timing.hash == hash) {
+            return Ok(prev.clone());
+        }
         let modified: DateTime<Utc> = metadata.modified()?.into();
-        let created: DateTime<Utc> = metadata.created()
-            .ok()
-            .map(|st| st.into())
-            .or_else(|| prev.map(|timing| timing.created.clone()))
+        let created: DateTime<Utc> = prev
+            .map(|timing| timing.created.clone())
+            .or_else(|| metadata.created().ok().map(|st| st.into()))
             .unwrap_or_else(|| modified.clone());
-        Ok(Timing { created, modified })
+        Ok(Timing {
+            created,
+            modified,
+            hash,
+            from_git: false,
+        })
     }
 }
 
-#[derive(Debug, Default, Deserialize, Serialize, PartialEq)]
+/// Everything remembered about a resource between builds
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
+pub struct ResourceMeta {
+    pub created: DateTime<Utc>,
+    pub modified: DateTime<Utc>,
+    #[serde(default)]
+    pub hash: String,
+    #[serde(default)]
+    pub from_git: bool,
+    /// Files built from the resource, relative to the build directory
+    #[serde(default)]
+    pub outputs: Vec<String>,
+    /// Whether the build listed the resource as a post, so other posts link to it
+    #[serde(default)]
+    pub listed: bool,
+}
+
+impl ResourceMeta {
+    pub fn new(timing: &Timing, outputs: Vec<String>, listed: bool) -> Self {
+        ResourceMeta {
+            created: timing.created.clone(),
+            modified: timing.modified.clone(),
+            hash: timing.hash.clone(),
+            from_git: timing.from_git,
+            outputs,
+            listed,
+        }
+    }
+
+    pub fn timing(&self) -> Timing {
+        Timing {
+            created: self.created.clone(),
+            modified: self.modified.clone(),
+            hash: self.hash.clone(),
+            from_git: self.from_git,
+        }
+    }
+}
+
+/// The metadata file, keyed by the path of each resource relative to the static directory
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
 pub struct Config {
-    pub timings: BTreeMap<String, Timing>,
+    version: i64,
+    /// `Site::hash` of the build, so a change to the site files or profile rebuilds everything
+    #[serde(default)]
+    pub site_hash: String,
+    /// Listing pages and other files built from every resource at once, relative to the build
+    /// directory. Values have to come before the resource tables in toml.
+    #[serde(default)]
+    pub generated: BTreeSet<String>,
+    pub resources: BTreeMap<String, ResourceMeta>,
+    /// Resources from a metadata file written before keys had extensions, keyed by section and
+    /// name, which resources without an entry of their own fall back to
+    #[serde(skip)]
+    legacy: BTreeMap<String, ResourceMeta>,
+}
+
+/// The first metadata file layout, a bare map of timings without a version
+#[derive(Debug, Deserialize)]
+struct ConfigV1 {
+    timings: BTreeMap<String, Timing>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config::new(BTreeMap::new())
+    }
 }
 
 impl Config {
-    pub fn new(timings: BTreeMap<String, Timing>) -> Self {
-        Config { timings }
+    pub fn new(resources: BTreeMap<String, ResourceMeta>) -> Self {
+        Config {
+            version: METADATA_VERSION,
+            site_hash: String::new(),
+            generated: BTreeSet::new(),
+            resources,
+            legacy: BTreeMap::new(),
+        }
+    }
+
+    /// The previous build's metadata of a resource, found by the key it had before version 3
+    /// when the metadata file hasn't been written since
+    pub fn get(&self, key: &str, legacy_key: &str) -> Option<&ResourceMeta> {
+        self.resources
+            .get(key)
+            .or_else(|| self.legacy.get(legacy_key))
     }
 
+    /// Every resource's metadata, legacy entries included
+    pub fn all_resources(&self) -> impl Iterator<Item = &ResourceMeta> {
+        self.resources.values().chain(self.legacy.values())
+    }
+
+    /// Reads a metadata file of any version, migrating older layouts to the current one
     pub fn from_file<P: AsRef<Path>>(config_file: P) -> OResult<Self> {
-        Ok(toml::from_slice(read_file(config_file)?.as_bytes())?)
+        Config::parse(&read_file(config_file)?)
+    }
+
+    fn parse(contents: &str) -> OResult<Self> {
+        let value: toml::Value = toml::from_str(contents)?;
+        let version = value.get("version").and_then(|v| v.as_integer()).unwrap_or(1);
+        match version {
+            METADATA_VERSION => Ok(value.try_into()?),
+            2 => {
+                info!("Migrating metadata file from version 2 to {}", METADATA_VERSION);
+                let mut config: Config = value.try_into()?;
+                config.version = METADATA_VERSION;
+                config.legacy = mem::replace(&mut config.resources, BTreeMap::new());
+                Ok(config)
+            }
+            1 => {
+                info!("Migrating metadata file from version 1 to {}", METADATA_VERSION);
+                let v1: ConfigV1 = value.try_into()?;
+                let legacy = v1
+                    .timings
+                    .into_iter()
+                    .map(|(key, timing)| (key, ResourceMeta::new(&timing, vec![], false)))
+                    .collect();
+                Ok(Config {
+                    legacy,
+                    ..Config::default()
+                })
+            }
+            _ => Err(OpaqueError::new(format!(
+                "Unsupported metadata file version {}, expected at most {}",
+                version, METADATA_VERSION
+            ))),
+        }
     }
 
     pub fn to_file<P: AsRef<Path>>(&self, config_file: P) -> OResult<()> {
         write_file(config_file, toml::ser::to_vec(self)?)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(rfc3339: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(rfc3339).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn migrates_version_1() {
+        let v1 = "[timings.about]\n\
+                  created = \"2018-07-04T12:30:00Z\"\n\
+                  modified = \"2018-07-05T12:30:00Z\"\n\
+                  \n\
+                  [timings.\"blog/coffee\"]\n\
+                  created = \"2018-08-01T08:00:00Z\"\n\
+                  modified = \"2018-08-01T08:00:00Z\"\n";
+        let config = Config::parse(v1).unwrap();
+        assert_eq!(config.version, METADATA_VERSION);
+        assert!(config.resources.is_empty());
+        assert_eq!(config.all_resources().count(), 2);
+        let about = config.get("pages/about.md", "about").unwrap();
+        assert_eq!(about.created, date("2018-07-04T12:30:00Z"));
+        assert_eq!(about.modified, date("2018-07-05T12:30:00Z"));
+        // without a hash the next build treats it as changed, keeping the created date
+        assert_eq!(about.hash, "");
+        assert!(about.outputs.is_empty());
+        assert!(config.get("blog/coffee.md", "blog/coffee").is_some());
+    }
+
+    #[test]
+    fn migrates_version_2() {
+        let v2 = "version = 2\n\
+                  site_hash = \"fedcba9876543210\"\n\
+                  generated = [\"index.html\"]\n\
+                  \n\
+                  [resources.\"blog/coffee\"]\n\
+                  created = \"2018-08-01T08:00:00Z\"\n\
+                  modified = \"2018-08-01T08:00:00Z\"\n\
+                  hash = \"0123456789abcdef\"\n\
+                  outputs = [\"blog/coffee.html\"]\n\
+                  listed = true\n";
+        let config = Config::parse(v2).unwrap();
+        assert_eq!(config.version, METADATA_VERSION);
+        assert_eq!(config.site_hash, "fedcba9876543210");
+        assert!(config.generated.contains("index.html"));
+        assert!(config.resources.is_empty());
+        let coffee = config.get("blog/coffee.md", "blog/coffee").unwrap();
+        assert_eq!(coffee.hash, "0123456789abcdef");
+        assert_eq!(coffee.outputs, vec!["blog/coffee.html".to_owned()]);
+        // a key of the current layout wins over the legacy one
+        let mut config = config.clone();
+        let timing = coffee.timing();
+        let meta = ResourceMeta::new(&timing, vec![], false);
+        config.resources.insert("blog/coffee.md".to_owned(), meta.clone());
+        assert_eq!(config.get("blog/coffee.md", "blog/coffee"), Some(&meta));
+    }
+
+    #[test]
+    fn round_trips_current_version() {
+        let timing = Timing {
+            created: date("2018-07-04T12:30:00Z"),
+            modified: date("2018-07-05T12:30:00Z"),
+            hash: "0123456789abcdef".to_owned(),
+            from_git: true,
+        };
+        let mut resources = BTreeMap::new();
+        let outputs = vec!["blog/coffee.html".to_owned(), "old/index.html".to_owned()];
+        resources.insert("coffee".to_owned(), ResourceMeta::new(&timing, outputs, true));
+        let mut config = Config::new(resources);
+        config.site_hash = "fedcba9876543210".to_owned();
+        config.generated.insert("index.html".to_owned());
+        config.generated.insert("page/2.html".to_owned());
+        let contents = toml::to_string(&config).unwrap();
+        assert_eq!(Config::parse(&contents).unwrap(), config);
+        assert_eq!(config.resources["coffee"].timing(), timing);
+    }
+
+    #[test]
+    fn rejects_unknown_version() {
+        let contents = format!("version = {}\n[resources]\n", METADATA_VERSION + 1);
+        assert!(Config::parse(&contents).is_err());
+    }
+}
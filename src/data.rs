@@ -0,0 +1,205 @@
+use errors::OResult;
+use inflector::cases::titlecase::to_title_case;
+use serde_json;
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::Path;
+use toml;
+use util::read_file;
+
+const TOML: &'static str = "toml";
+const JSON: &'static str = "json";
+
+/// A value from a data file, keeping its nesting so templates can walk it
+#[derive(Debug, Clone, PartialEq)]
+pub enum DataValue {
+    /// Strings, numbers, booleans and dates
+    Text(String),
+    List(Vec<DataValue>),
+    Table(Vec<DataField>),
+}
+
+impl DataValue {
+    fn from_toml(value: &toml::Value) -> Self {
+        match *value {
+            toml::Value::String(ref s) => DataValue::Text(s.clone()),
+            toml::Value::Array(ref items) => {
+                DataValue::List(items.iter().map(DataValue::from_toml).collect())
+            }
+            toml::Value::Table(ref table) => DataValue::Table(
+                table
+                    .iter()
+                    .map(|(key, value)| DataField::new(key, DataValue::from_toml(value)))
+                    .collect(),
+            ),
+            ref other => DataValue::Text(other.to_string()),
+        }
+    }
+
+    /// Nulls have nothing to show, so they're left out wherever they turn up
+    fn from_json(value: &serde_json::Value) -> Option<Self> {
+        use serde_json::Value;
+        match *value {
+            Value::Null => None,
+            Value::String(ref s) => Some(DataValue::Text(s.clone())),
+            Value::Array(ref items) => Some(DataValue::List(
+                items.iter().filter_map(DataValue::from_json).collect(),
+            )),
+            Value::Object(ref map) => Some(DataValue::Table(
+                map.iter()
+                    .filter_map(|(key, value)| {
+                        DataValue::from_json(value).map(|value| DataField::new(key, value))
+                    }).collect(),
+            )),
+            ref other => Some(DataValue::Text(other.to_string())),
+        }
+    }
+
+    /// Html for the value, lists as `<ul>` and tables as `<dl>`, for templates that don't need
+    /// to pick it apart
+    pub fn to_html(&self) -> String {
+        match *self {
+            DataValue::Text(ref text) => escape_html(text),
+            DataValue::List(ref items) => {
+                let items = items
+                    .iter()
+                    .map(|item| format!("<li>{}</li>", item.to_html()))
+                    .collect::<String>();
+                format!("<ul>{}</ul>", items)
+            }
+            DataValue::Table(ref fields) => {
+                let fields = fields
+                    .iter()
+                    .map(|field| {
+                        let key = escape_html(&field.key);
+                        format!("<dt>{}</dt><dd>{}</dd>", key, field.value.to_html())
+                    }).collect::<String>();
+                format!("<dl>{}</dl>", fields)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataField {
+    pub key: String,
+    pub value: DataValue,
+}
+
+impl DataField {
+    fn new(key: &str, value: DataValue) -> Self {
+        DataField {
+            key: key.to_owned(),
+            value,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataEntry {
+    pub fields: Vec<DataField>,
+}
+
+impl DataEntry {
+    fn from_value(value: DataValue) -> Self {
+        let fields = match value {
+            DataValue::Table(fields) => fields,
+            other => vec![DataField::new("value", other)],
+        };
+        DataEntry { fields }
+    }
+}
+
+/// One file from the data directory, named after its file stem
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataSet {
+    pub name: String,
+    pub title: String,
+    pub entries: Vec<DataEntry>,
+}
+
+impl DataSet {
+    fn new(name: String, value: DataValue) -> Self {
+        DataSet {
+            title: to_title_case(&name),
+            name,
+            entries: entries(value),
+        }
+    }
+}
+
+/// Structured toml and json files from the data directory, available to every template
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SiteData {
+    sets: BTreeMap<String, DataSet>,
+}
+
+impl SiteData {
+    pub fn from_dir<P: AsRef<Path>>(data_dir: P) -> OResult<Self> {
+        let dir = data_dir.as_ref();
+        let mut sets = BTreeMap::new();
+        if !dir.is_dir() {
+            info!("No data directory at {:?}, skipping site data", dir);
+            return Ok(SiteData { sets });
+        }
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let name = match path.file_stem().and_then(OsStr::to_str) {
+                Some(name) if !name.starts_with('.') => name.to_owned(),
+                _ => continue,
+            };
+            let value = match path.extension().and_then(OsStr::to_str) {
+                Some(TOML) => {
+                    let value: toml::Value = toml::from_str(&read_file(&path)?)?;
+                    DataValue::from_toml(&value)
+                }
+                Some(JSON) => {
+                    let value: serde_json::Value = serde_json::from_str(&read_file(&path)?)?;
+                    DataValue::from_json(&value).unwrap_or_else(|| DataValue::List(vec![]))
+                }
+                _ => {
+                    info!("Skipping data file due to unknown extension: {:?}", path);
+                    continue;
+                }
+            };
+            info!("Read data file {:?}", path);
+            sets.insert(name.clone(), DataSet::new(name, value));
+        }
+        Ok(SiteData { sets })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&DataSet> {
+        self.sets.get(name)
+    }
+}
+
+// A list of tables reads as one entry per table. A file holding nothing but a single list, the
+// way `[[talks]]` reads in toml, unwraps to that list.
+fn entries(value: DataValue) -> Vec<DataEntry> {
+    let value = match value {
+        DataValue::Table(mut fields) => {
+            if fields.len() == 1 {
+                let field = fields.remove(0);
+                match field.value {
+                    DataValue::List(items) => DataValue::List(items),
+                    value => DataValue::Table(vec![DataField { key: field.key, value }]),
+                }
+            } else {
+                DataValue::Table(fields)
+            }
+        }
+        value => value,
+    };
+    match value {
+        DataValue::List(items) => items.into_iter().map(DataEntry::from_value).collect(),
+        value => vec![DataEntry::from_value(value)],
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
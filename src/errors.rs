@@ -1,4 +1,4 @@
-use super::{clap, toml, askama, html5ever_ext, image, simplelog};
+use super::{chrono, clap, toml, askama, html5ever_ext, image, serde_json, simplelog};
 use std::fmt::{self};
 use std::io;
 use std::sync::mpsc;
@@ -35,6 +35,7 @@ opaque_error!(io::Error);
 opaque_error!(clap::Error);
 opaque_error!(toml::de::Error);
 opaque_error!(toml::ser::Error);
+opaque_error!(serde_json::Error);
 opaque_error!(askama::Error);
 opaque_error!(html5ever_ext::HtmlError);
 opaque_error!(image::ImageError);
@@ -42,5 +43,7 @@ opaque_error!(simplelog::TermLogError);
 opaque_error!(notify::Error);
 opaque_error!(mpsc::RecvError);
 opaque_error!(std::string::FromUtf8Error);
+opaque_error!(chrono::ParseError);
+opaque_error!(std::num::ParseIntError);
 
 pub type OResult<T> = Result<T, OpaqueError>;
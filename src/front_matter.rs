@@ -0,0 +1,178 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use errors::{OResult, OpaqueError};
+use toml;
+use toml::value::Datetime;
+
+const DELIMITER: &'static str = "+++";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawFrontMatter {
+    title: Option<String>,
+    subtitle: Option<String>,
+    description: Option<String>,
+    date: Option<Datetime>,
+    updated: Option<Datetime>,
+    tags: Vec<String>,
+    draft: bool,
+    image: Option<String>,
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    series: Option<String>,
+    series_part: Option<usize>,
+    toc: bool,
+    author: Option<String>,
+    aliases: Vec<String>,
+    pinned: bool,
+    weight: i64,
+    featured: bool,
+    data: Vec<String>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FrontMatter {
+    pub title: Option<String>,
+    pub subtitle: Option<String>,
+    pub description: Option<String>,
+    pub date: Option<DateTime<Utc>>,
+    pub updated: Option<DateTime<Utc>>,
+    pub tags: Vec<String>,
+    pub draft: bool,
+    pub image: Option<String>,
+    pub kind: Option<String>,
+    pub series: Option<String>,
+    pub series_part: Option<usize>,
+    pub toc: bool,
+    pub author: Option<String>,
+    /// Old paths that should redirect here
+    pub aliases: Vec<String>,
+    /// Keeps the post at the top of the index, ordered by ascending weight
+    pub pinned: bool,
+    pub weight: i64,
+    /// Shows the post and its image in the index banner
+    pub featured: bool,
+    /// Data sets listed below the page's content
+    pub data: Vec<String>,
+}
+
+impl FrontMatter {
+    fn from_raw(raw: RawFrontMatter) -> OResult<Self> {
+        Ok(FrontMatter {
+            title: raw.title,
+            subtitle: raw.subtitle,
+            description: raw.description,
+            date: optional_utc(&raw.date)?,
+            updated: optional_utc(&raw.updated)?,
+            tags: raw.tags,
+            draft: raw.draft,
+            image: raw.image,
+            kind: raw.kind,
+            series: raw.series,
+            series_part: raw.series_part,
+            toc: raw.toc,
+            author: raw.author,
+            aliases: raw.aliases,
+            pinned: raw.pinned,
+            weight: raw.weight,
+            featured: raw.featured,
+            data: raw.data,
+        })
+    }
+}
+
+/// Splits a `+++` delimited toml block off the top of a markdown file. Files without one get
+/// the default front matter and are returned untouched.
+pub fn split(buf: &str) -> OResult<(FrontMatter, &str)> {
+    let trimmed = buf.trim_left();
+    if !trimmed.starts_with(DELIMITER) {
+        return Ok((FrontMatter::default(), buf));
+    }
+    let rest = &trimmed[DELIMITER.len()..];
+    let end = rest
+        .find(&format!("\n{}", DELIMITER))
+        .ok_or(OpaqueError::new("Unterminated front matter block"))?;
+    // a bare carriage return isn't valid toml, so drop the one a CRLF file leaves behind
+    let raw: RawFrontMatter = toml::from_str(rest[..end].trim_right_matches('\r'))?;
+    let after = &rest[end + 1 + DELIMITER.len()..];
+    let body = match after.find('\n') {
+        Some(i) => &after[i + 1..],
+        None => "",
+    };
+    Ok((FrontMatter::from_raw(raw)?, body))
+}
+
+fn optional_utc(datetime: &Option<Datetime>) -> OResult<Option<DateTime<Utc>>> {
+    match datetime {
+        Some(dt) => Ok(Some(to_utc(dt)?)),
+        None => Ok(None),
+    }
+}
+
+// toml datetimes may be full offset datetimes, local datetimes, or bare dates
+fn to_utc(datetime: &Datetime) -> OResult<DateTime<Utc>> {
+    let s = datetime.to_string();
+    if let Ok(dt) = DateTime::parse_from_rfc3339(&s) {
+        return Ok(dt.with_timezone(&Utc));
+    }
+    if let Ok(naive) = NaiveDateTime::parse_from_str(&s, "%Y-%m-%dT%H:%M:%S%.f") {
+        return Ok(DateTime::from_utc(naive, Utc));
+    }
+    let date = NaiveDate::parse_from_str(&s, "%Y-%m-%d")?;
+    Ok(DateTime::from_utc(date.and_hms(0, 0, 0), Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_block() {
+        let (front_matter, body) = split("# Hello\n\n+++ not front matter").unwrap();
+        assert_eq!(front_matter, FrontMatter::default());
+        assert_eq!(body, "# Hello\n\n+++ not front matter");
+    }
+
+    #[test]
+    fn block() {
+        let buf = "+++\ntitle = \"Why \\\"unsafe\\\" & <T>\"\ntags = [\"rust\"]\n+++\n# Hello\n";
+        let (front_matter, body) = split(buf).unwrap();
+        assert_eq!(front_matter.title, Some("Why \"unsafe\" & <T>".to_owned()));
+        assert_eq!(front_matter.tags, vec!["rust".to_owned()]);
+        assert_eq!(body, "# Hello\n");
+    }
+
+    #[test]
+    fn unterminated_block() {
+        assert!(split("+++\ntitle = \"Hello\"\n# Hello\n").is_err());
+    }
+
+    #[test]
+    fn unknown_key() {
+        assert!(split("+++\ndrafts = true\n+++\n# Hello\n").is_err());
+    }
+
+    #[test]
+    fn crlf() {
+        let buf = "+++\r\ntitle = \"Hello\"\r\ndraft = true\r\n+++\r\n# Hello\r\n";
+        let (front_matter, body) = split(buf).unwrap();
+        assert_eq!(front_matter.title, Some("Hello".to_owned()));
+        assert!(front_matter.draft);
+        assert_eq!(body, "# Hello\r\n");
+    }
+
+    fn date(buf: &str) -> DateTime<Utc> {
+        split(buf).unwrap().0.date.unwrap()
+    }
+
+    fn utc(rfc3339: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(rfc3339).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn dates() {
+        let midday = utc("2018-07-04T12:30:00Z");
+        assert_eq!(date("+++\ndate = 2018-07-04\n+++\n"), utc("2018-07-04T00:00:00Z"));
+        assert_eq!(date("+++\ndate = 2018-07-04T12:30:00\n+++\n"), midday);
+        assert_eq!(date("+++\ndate = 2018-07-04T14:30:00+02:00\n+++\n"), midday);
+    }
+}
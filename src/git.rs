@@ -0,0 +1,114 @@
+use chrono::{DateTime, Utc};
+use errors::{OResult, OpaqueError};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Commit dates of a single file
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileDates {
+    /// When the file was first committed
+    pub created: DateTime<Utc>,
+    /// When the file was last committed, unless it has uncommitted edits since
+    pub modified: Option<DateTime<Utc>>,
+}
+
+/// The git repository a static directory lives in, whose history is read whenever the metadata
+/// file is missing the commit dates of a file. Renames aren't followed, so a moved file starts
+/// over.
+#[derive(Debug)]
+pub struct GitDates {
+    root: PathBuf,
+    dir: PathBuf,
+}
+
+impl GitDates {
+    pub fn from_repo<P: AsRef<Path>>(dir: P) -> OResult<Self> {
+        let dir = dir.as_ref().canonicalize()?;
+        let root = git(&dir, &["rev-parse", "--show-toplevel"])?;
+        let root = PathBuf::from(root.trim()).canonicalize()?;
+        info!("Reading commit dates from {:?}", root);
+        Ok(GitDates { root, dir })
+    }
+
+    /// Commit dates of every file in the static directory, read with one pass over the log and
+    /// one look at the working tree
+    pub fn history(&self) -> OResult<History> {
+        let dir = self.dir.to_string_lossy().into_owned();
+        let log = git(
+            &self.root,
+            &["log", "--format=%x00%aI", "--name-only", "--no-renames", "--", dir.as_str()],
+        )?;
+        // newest commits come first, so the last date seen for a file is its first commit
+        let mut commits: BTreeMap<PathBuf, (DateTime<Utc>, DateTime<Utc>)> = BTreeMap::new();
+        for commit in log.split('\0').filter(|commit| !commit.is_empty()) {
+            let mut lines = commit.lines();
+            let date = DateTime::parse_from_rfc3339(lines.next().unwrap_or(""))?;
+            let date = date.with_timezone(&Utc);
+            for file in lines.filter(|line| !line.is_empty()) {
+                commits
+                    .entry(self.root.join(file))
+                    .or_insert((date, date))
+                    .0 = date;
+            }
+        }
+        let status = git(&self.root, &["status", "--porcelain", "-z", "--", dir.as_str()])?;
+        let mut uncommitted = BTreeSet::new();
+        let mut entries = status.split('\0').filter(|entry| entry.len() > 3);
+        while let Some(entry) = entries.next() {
+            let (state, file) = entry.split_at(3);
+            // renames and copies are followed by the path they came from
+            if state.contains('R') || state.contains('C') {
+                entries.next();
+            }
+            uncommitted.insert(self.root.join(file));
+        }
+        Ok(History {
+            commits,
+            uncommitted,
+        })
+    }
+}
+
+/// Commit dates read from the repository at one point in time
+#[derive(Debug)]
+pub struct History {
+    /// When each file was first and last committed
+    commits: BTreeMap<PathBuf, (DateTime<Utc>, DateTime<Utc>)>,
+    /// Files with changes that aren't committed yet
+    uncommitted: BTreeSet<PathBuf>,
+}
+
+impl History {
+    /// The commit dates of a file, if it has ever been committed
+    pub fn get(&self, path: &Path) -> OResult<Option<FileDates>> {
+        let path = path.canonicalize()?;
+        Ok(self
+            .commits
+            .get(&path)
+            .map(|&(created, last_commit)| FileDates {
+                created,
+                modified: if self.uncommitted.contains(&path) {
+                    None
+                } else {
+                    Some(last_commit)
+                },
+            }))
+    }
+}
+
+fn git(dir: &Path, args: &[&str]) -> OResult<String> {
+    let output = Command::new("git")
+        .args(&["-c", "core.quotepath=off"])
+        .args(args)
+        .current_dir(dir)
+        .output()?;
+    if !output.status.success() {
+        return Err(OpaqueError::new(format!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8(output.stdout)?)
+}
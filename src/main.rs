@@ -2,6 +2,7 @@
 extern crate askama;
 extern crate chrono;
 extern crate clap;
+extern crate flate2;
 #[macro_use]
 extern crate html5ever;
 extern crate html5ever_ext;
@@ -18,20 +19,29 @@ extern crate simplelog;
 #[macro_use]
 extern crate serde_derive;
 extern crate serde;
+extern crate serde_json;
 extern crate syntect;
 extern crate toml;
+mod authors;
 mod config;
+mod data;
 mod errors;
+mod front_matter;
+mod git;
+mod markdown;
 mod resource;
 mod serve;
+mod site;
 mod templates;
 mod util;
 
 use clap::{App, Arg, SubCommand};
 use config::*;
 use errors::*;
-use resource::SiteResources;
+use git::GitDates;
+use resource::{ReadOptions, SiteResources};
 use serve::serve;
+use site::{Site, SiteFiles};
 use std::fs;
 use std::path::{Path, PathBuf};
 
@@ -51,13 +61,22 @@ use std::path::{Path, PathBuf};
  * TODO: Add a deploy subcommand that takes care of the scp step
  */
 
+const DEFAULT_PAGE_SIZE: usize = 10;
+
 fn main() -> Result<(), OpaqueError> {
     simplelog::TermLogger::init(simplelog::LevelFilter::Info, simplelog::Config::default())?;
+    let arg_authors = "AUTHORS_FILE";
     let arg_build = "BUILD_DIR";
     let arg_cache = "NO_CACHE";
     let arg_clean = "CLEAN";
+    let arg_data = "DATA_DIR";
+    let arg_drafts = "DRAFTS";
+    let arg_git_dates = "GIT_DATES";
     let arg_listen = "LISTEN_ADDR";
     let arg_metadata = "METADATA_FILE";
+    let arg_page_size = "PAGE_SIZE";
+    let arg_profile = "PROFILE";
+    let arg_site = "SITE_FILE";
     let arg_static = "STATIC_DIR";
     let mut app = App::new("static-site-generator")
         .version("1.0")
@@ -75,6 +94,36 @@ fn main() -> Result<(), OpaqueError> {
                 .help("The location of the metadata file (stores time info)")
                 .takes_value(true)
                 .global(true),
+        ).arg(
+            Arg::with_name(arg_authors)
+                .long("authors")
+                .help("The location of the authors file (defaults to authors.toml in static)")
+                .takes_value(true)
+                .global(true),
+        ).arg(
+            Arg::with_name(arg_site)
+                .long("site")
+                .help("The location of the site config file (defaults to site.toml in static)")
+                .takes_value(true)
+                .global(true),
+        ).arg(
+            Arg::with_name(arg_data)
+                .long("data")
+                .help("The directory of toml and json data files (defaults to data in static)")
+                .takes_value(true)
+                .global(true),
+        ).arg(
+            Arg::with_name(arg_page_size)
+                .long("page-size")
+                .help("The number of posts per listing page, 0 for no pagination")
+                .takes_value(true)
+                .global(true),
+        ).arg(
+            Arg::with_name(arg_git_dates)
+                .long("git-dates")
+                .help("Takes created and modified dates from git history instead of the filesystem")
+                .takes_value(false)
+                .global(true),
         ).arg(
             Arg::with_name(arg_build)
                 .index(1)
@@ -93,6 +142,12 @@ fn main() -> Result<(), OpaqueError> {
                         .long("no-cache")
                         .help("rebuilds all files regardless of timing")
                         .takes_value(false),
+                ).arg(
+                    Arg::with_name(arg_profile)
+                        .long("profile")
+                        .help("how to build the site (defaults to prod)")
+                        .possible_values(&["dev", "prod"])
+                        .takes_value(true),
                 ),
         ).subcommand(
             SubCommand::with_name("serve")
@@ -102,13 +157,24 @@ fn main() -> Result<(), OpaqueError> {
                         .index(1)
                         .help("The address and port to listen on")
                         .takes_value(true),
+                ).arg(
+                    Arg::with_name(arg_drafts)
+                        .long("drafts")
+                        .help("includes drafts and scheduled posts for preview")
+                        .takes_value(false),
+                ).arg(
+                    Arg::with_name(arg_profile)
+                        .long("profile")
+                        .help("how to build the site (defaults to dev)")
+                        .possible_values(&["dev", "prod"])
+                        .takes_value(true),
                 ),
         );
     let matches = app.clone().get_matches();
 
-    match matches.subcommand() {
-        ("build", Some(_build_matches)) => {}
-        ("serve", Some(_serve_matches)) => {}
+    let profile_name = match matches.subcommand() {
+        ("build", Some(build_matches)) => build_matches.value_of(arg_profile).unwrap_or("prod"),
+        ("serve", Some(serve_matches)) => serve_matches.value_of(arg_profile).unwrap_or("dev"),
         _ => {
             app.print_help()?;
             println!();
@@ -124,23 +190,80 @@ fn main() -> Result<(), OpaqueError> {
         .map(|s| PathBuf::from(s))
         .unwrap_or(static_dir.join(".meta.toml"));
 
-    let config = Config::from_file(&metadata_file).unwrap_or(Config::default());
+    let page_size = matches
+        .value_of(arg_page_size)
+        .map(|s| s.parse::<usize>())
+        .unwrap_or(Ok(DEFAULT_PAGE_SIZE))?;
+
+    let site_file = matches
+        .value_of(arg_site)
+        .map(|s| PathBuf::from(s))
+        .unwrap_or(static_dir.join("site.toml"));
+    let authors_file = matches
+        .value_of(arg_authors)
+        .map(|s| PathBuf::from(s))
+        .unwrap_or(static_dir.join("authors.toml"));
+    let data_dir = matches
+        .value_of(arg_data)
+        .map(|s| PathBuf::from(s))
+        .unwrap_or(static_dir.join("data"));
+    let site = Site::from_files(SiteFiles {
+        site_file,
+        authors_file,
+        data_dir,
+        profile: profile_name.to_owned(),
+    })?;
+
+    let config = if metadata_file.exists() {
+        Config::from_file(&metadata_file).map_err(|e| {
+            OpaqueError::new(format!("Invalid metadata file {:?}: {}", metadata_file, e))
+        })?
+    } else {
+        info!("No metadata file at {:?}, treating every resource as new", metadata_file);
+        Config::default()
+    };
+    let git_dates = if matches.is_present(arg_git_dates) {
+        match GitDates::from_repo(static_dir) {
+            Ok(git_dates) => Some(git_dates),
+            Err(e) => {
+                warn!("Could not read git history, using the metadata file instead: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
 
     match matches.subcommand() {
         ("build", Some(build_matches)) => {
-            let resources = SiteResources::read_resources(&static_dir, &config)?;
+            let options = ReadOptions {
+                include_drafts: site.profile.drafts,
+                git_dates,
+            };
+            let resources = SiteResources::read_resources(&static_dir, &config, &site, &options)?;
             if build_matches.is_present(arg_clean) {
                 warn!("Cleaning build_dir {:?}", build_dir);
                 fs::remove_dir_all(build_dir)?;
             }
-            resources.build_all(build_dir, build_matches.is_present(arg_cache))?;
-            let updated_config = Config::new(resources.timings());
+            resources.build_all(build_dir, build_matches.is_present(arg_cache), page_size)?;
+            let updated_config = resources.metadata(build_dir, &config)?;
             if config != updated_config {
                 updated_config.to_file(&metadata_file)?;
             }
             Ok(())
         }
-        ("serve", Some(_serve_matches)) => serve(&config, &build_dir, &static_dir, &metadata_file),
+        ("serve", Some(serve_matches)) => serve(
+            &config,
+            &build_dir,
+            &static_dir,
+            &metadata_file,
+            &site,
+            &ReadOptions {
+                include_drafts: site.profile.drafts || serve_matches.is_present(arg_drafts),
+                git_dates,
+            },
+            page_size,
+        ),
         _ => unreachable!(),
     }
 }
@@ -0,0 +1,365 @@
+use pulldown_cmark::{html, Event, Options, Parser, Tag};
+use pulldown_cmark::{OPTION_ENABLE_FOOTNOTES, OPTION_ENABLE_TABLES};
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+
+const MORE_MARKER: &'static str = "<!-- more -->";
+const WORDS_PER_MINUTE: usize = 200;
+const TOC_LEVELS: (i32, i32) = (2, 4);
+/// Ids the templates already use, which headings can't take
+const RESERVED_IDS: &'static [&'static str] = &[
+    "archive",
+    "author",
+    "blog",
+    "blog-previews",
+    "body",
+    "content",
+    "copyright",
+    "featured",
+    "footer",
+    "header",
+    "index-welcome",
+    "photo-gallery",
+    "related",
+    "section",
+    "tags",
+    "title",
+];
+
+#[derive(Debug, Clone)]
+pub struct Heading {
+    pub level: i32,
+    pub id: String,
+    /// Plain heading text, escaped by the template
+    pub title: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct TocEntry {
+    pub level: i32,
+    pub id: String,
+    pub title: String,
+    pub children: Vec<TocEntry>,
+}
+
+fn options() -> Options {
+    let mut opts = Options::empty();
+    opts.insert(OPTION_ENABLE_FOOTNOTES);
+    opts.insert(OPTION_ENABLE_TABLES);
+    opts
+}
+
+pub fn render(markdown: &str) -> String {
+    render_with_headings(markdown).0
+}
+
+/// Renders the markdown with a unique id and a `#` permalink on every heading, returning the
+/// headings in document order alongside the html
+pub fn render_with_headings(markdown: &str) -> (String, Vec<Heading>) {
+    render_rebased(markdown, "", &[])
+}
+
+/// Like `render_with_headings`, with relative links pointed at `base`, and heading ids kept
+/// clear of the `reserved` ids the page gives other elements
+pub fn render_rebased(markdown: &str, base: &str, reserved: &[&str]) -> (String, Vec<Heading>) {
+    let mut events = vec![];
+    let mut headings = vec![];
+    let mut ids = RESERVED_IDS
+        .iter()
+        .chain(reserved)
+        .map(|id| id.to_string())
+        .collect::<BTreeSet<_>>();
+    let mut heading_events: Option<Vec<Event>> = None;
+    for event in Parser::new_ext(markdown, options()).map(|event| rebase(event, base)) {
+        match event {
+            Event::Start(Tag::Header(_)) => heading_events = Some(vec![]),
+            Event::End(Tag::Header(level)) => {
+                let inner = heading_events.take().unwrap_or_default();
+                let text = inner
+                    .iter()
+                    .filter_map(|event| match *event {
+                        Event::Text(ref text) => Some(&text[..]),
+                        _ => None,
+                    }).collect::<String>();
+                let id = unique_id(&slugify(&text), &mut ids);
+                events.push(Event::Html(format!("<h{} id=\"{}\">", level, id).into()));
+                events.extend(inner);
+                events.push(Event::Html(permalink(&id).into()));
+                events.push(Event::Html(format!("</h{}>\n", level).into()));
+                headings.push(Heading {
+                    level,
+                    id,
+                    title: text,
+                });
+            }
+            event => match heading_events {
+                Some(ref mut inner) => inner.push(event),
+                None => events.push(event),
+            },
+        }
+    }
+    let mut html_buf = String::new();
+    html::push_html(&mut html_buf, events.into_iter());
+    (html_buf, headings)
+}
+
+/// Nests the h2 through h4 headings under their closest preceding parent heading
+pub fn toc(headings: &[Heading]) -> Vec<TocEntry> {
+    let mut entries = vec![];
+    for heading in headings
+        .iter()
+        .filter(|h| h.level >= TOC_LEVELS.0 && h.level <= TOC_LEVELS.1)
+    {
+        insert_toc_entry(
+            &mut entries,
+            TocEntry {
+                level: heading.level,
+                id: heading.id.clone(),
+                title: heading.title.clone(),
+                children: vec![],
+            },
+        );
+    }
+    entries
+}
+
+fn insert_toc_entry(entries: &mut Vec<TocEntry>, entry: TocEntry) {
+    let nested = entries.last().map_or(false, |last| last.level < entry.level);
+    if nested {
+        let last = entries.last_mut().unwrap();
+        insert_toc_entry(&mut last.children, entry);
+    } else {
+        entries.push(entry);
+    }
+}
+
+fn permalink(id: &str) -> String {
+    format!(
+        "<a class=\"permalink\" href=\"#{}\" aria-label=\"Link to this section\">#</a>",
+        id
+    )
+}
+
+/// Lowercase alphanumerics separated by single dashes
+fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+    for c in text.chars().flat_map(|c| c.to_lowercase()) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let trimmed = slug.trim_right_matches('-').to_owned();
+    if trimmed.is_empty() {
+        "section".to_owned()
+    } else {
+        trimmed
+    }
+}
+
+/// Suffixes repeated slugs with a counter so every id on the page is distinct
+fn unique_id(slug: &str, ids: &mut BTreeSet<String>) -> String {
+    let mut id = slug.to_owned();
+    let mut suffix = 1;
+    while ids.contains(&id) {
+        id = format!("{}-{}", slug, suffix);
+        suffix += 1;
+    }
+    ids.insert(id.clone());
+    id
+}
+
+/// Prefixes a relative url with `base`, leaving absolute urls, fragments and anything with a
+/// scheme alone
+pub fn rebase_url(url: &str, base: &str) -> String {
+    if base.is_empty() || url.starts_with('/') || url.starts_with('#') || url.contains(':') {
+        url.to_owned()
+    } else {
+        format!("{}/{}", base.trim_right_matches('/'), url.trim_left_matches("./"))
+    }
+}
+
+fn rebase<'a>(event: Event<'a>, base: &str) -> Event<'a> {
+    if base.is_empty() {
+        return event;
+    }
+    match event {
+        Event::Start(Tag::Link(url, title)) => {
+            Event::Start(Tag::Link(Cow::from(rebase_url(&url, base)), title))
+        }
+        Event::Start(Tag::Image(url, title)) => {
+            Event::Start(Tag::Image(Cow::from(rebase_url(&url, base)), title))
+        }
+        event => event,
+    }
+}
+
+/// Renders everything above a `<!-- more -->` marker, or just the first paragraph if there
+/// isn't one. Relative links are rebased onto `base`, since summaries are shown on other pages,
+/// and headings get no ids or permalinks, which would point at the listing showing them.
+pub fn summarize(markdown: &str, base: &str) -> String {
+    let events = Parser::new_ext(markdown, options())
+        .map(|event| rebase(event, base))
+        .collect::<Vec<_>>();
+    // only a marker of its own counts, not one quoted in a code block or nested in a list
+    let mut depth = 0;
+    let more = events.iter().position(|event| {
+        match *event {
+            Event::Start(_) => depth += 1,
+            Event::End(_) => depth -= 1,
+            Event::Html(ref html) => return depth == 0 && html.trim() == MORE_MARKER,
+            _ => {}
+        }
+        false
+    });
+    let mut html_buf = String::new();
+    if let Some(i) = more {
+        html::push_html(&mut html_buf, events.into_iter().take(i));
+        return html_buf;
+    }
+    let first_paragraph = events
+        .into_iter()
+        .skip_while(|event| match *event {
+            Event::Start(Tag::Paragraph) => false,
+            _ => true,
+        }).take_while(|event| match *event {
+            Event::End(Tag::Paragraph) => false,
+            _ => true,
+        }).skip(1);
+    html::push_html(&mut html_buf, first_paragraph);
+    if html_buf.is_empty() {
+        html_buf
+    } else {
+        format!("<p>{}</p>", html_buf)
+    }
+}
+
+/// Counts the words in the prose, leaving out code blocks
+pub fn word_count(markdown: &str) -> usize {
+    let mut in_code_block = false;
+    let mut count = 0;
+    for event in Parser::new_ext(markdown, options()) {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => in_code_block = true,
+            Event::End(Tag::CodeBlock(_)) => in_code_block = false,
+            Event::Text(ref text) if !in_code_block => count += text.split_whitespace().count(),
+            _ => {}
+        }
+    }
+    count
+}
+
+/// Estimated minutes to read, never less than one
+pub fn reading_time(word_count: usize) -> usize {
+    ((word_count + WORDS_PER_MINUTE - 1) / WORDS_PER_MINUTE).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn heading(level: i32, id: &str) -> Heading {
+        Heading {
+            level,
+            id: id.to_owned(),
+            title: id.to_owned(),
+        }
+    }
+
+    #[test]
+    fn slugs() {
+        assert_eq!(slugify("Hello, World!"), "hello-world");
+        assert_eq!(slugify("  Über  Café "), "über-café");
+        assert_eq!(slugify("C++ & Rust"), "c-rust");
+        assert_eq!(slugify("?!"), "section");
+    }
+
+    #[test]
+    fn unique_ids() {
+        let mut ids = BTreeSet::new();
+        assert_eq!(unique_id("intro", &mut ids), "intro");
+        assert_eq!(unique_id("intro", &mut ids), "intro-1");
+        assert_eq!(unique_id("intro", &mut ids), "intro-2");
+        assert_eq!(unique_id("outro", &mut ids), "outro");
+    }
+
+    #[test]
+    fn heading_ids() {
+        let (html_buf, headings) = render_with_headings("# Setup\n\n## Setup\n\n## Content\n");
+        let ids = headings.iter().map(|h| h.id.as_str()).collect::<Vec<_>>();
+        // content is one of the template's ids
+        assert_eq!(ids, vec!["setup", "setup-1", "content-1"]);
+        let permalink = "<a class=\"permalink\" href=\"#setup-1\"";
+        assert!(html_buf.contains(&format!("<h2 id=\"setup-1\">Setup{}", permalink)));
+    }
+
+    #[test]
+    fn reserved_heading_ids() {
+        let (_, headings) = render_rebased("## About\n\n## Talks\n", "", &["about", "talks"]);
+        let ids = headings.iter().map(|h| h.id.as_str()).collect::<Vec<_>>();
+        assert_eq!(ids, vec!["about-1", "talks-1"]);
+    }
+
+    #[test]
+    fn toc_nesting() {
+        let headings = vec![
+            heading(1, "title"),
+            heading(2, "a"),
+            heading(3, "a-1"),
+            heading(4, "a-1-1"),
+            heading(3, "a-2"),
+            heading(5, "too-deep"),
+            heading(2, "b"),
+        ];
+        let entries = toc(&headings);
+        let ids = entries.iter().map(|e| e.id.as_str()).collect::<Vec<_>>();
+        assert_eq!(ids, vec!["a", "b"]);
+        let children = &entries[0].children;
+        assert_eq!(children.len(), 2);
+        assert_eq!(children[0].children[0].id, "a-1-1");
+        assert!(children[1].children.is_empty());
+        assert!(entries[1].children.is_empty());
+    }
+
+    #[test]
+    fn word_counts() {
+        assert_eq!(word_count("# Two words\n\nAnd *three* more.\n"), 5);
+        assert_eq!(word_count("One\n\n```\nlet code = not_words();\n```\n"), 1);
+    }
+
+    #[test]
+    fn reading_times() {
+        assert_eq!(reading_time(0), 1);
+        assert_eq!(reading_time(WORDS_PER_MINUTE), 1);
+        assert_eq!(reading_time(WORDS_PER_MINUTE + 1), 2);
+    }
+
+    #[test]
+    fn summary_is_first_paragraph() {
+        let summary = summarize("# Title\n\nFirst *post*.\n\nSecond paragraph.\n", "");
+        assert_eq!(summary, "<p>First <em>post</em>.</p>");
+    }
+
+    #[test]
+    fn summary_stops_at_more_marker() {
+        let markdown = "## Intro\n\nOne.\n\nTwo.\n\n<!-- more -->\n\nThree.\n";
+        let summary = summarize(markdown, "");
+        assert_eq!(summary, "<h2>Intro</h2>\n<p>One.</p>\n<p>Two.</p>\n");
+    }
+
+    #[test]
+    fn summary_ignores_quoted_more_marker() {
+        let markdown = "Use this:\n\n```\n<!-- more -->\n```\n\nMore.\n";
+        assert_eq!(summarize(markdown, ""), "<p>Use this:</p>");
+    }
+
+    #[test]
+    fn summary_rebases_links() {
+        let summary = summarize("![Fig](fig.png) and [docs](/docs)\n", "/blog/post");
+        assert_eq!(
+            summary,
+            "<p><img src=\"/blog/post/fig.png\" alt=\"Fig\" /> and <a href=\"/docs\">docs</a></p>"
+        );
+    }
+}
@@ -1,15 +1,27 @@
 use askama::Template;
-use config::{Config, Timing};
+use authors::{Author, Authors};
+use config::{Config, ResourceMeta, Timing};
+use chrono::{DateTime, Datelike, Utc};
 use errors::{OResult, OpaqueError};
+use front_matter::{self, FrontMatter};
+use git::GitDates;
+use inflector::cases::kebabcase::to_kebab_case;
 use inflector::cases::titlecase::to_title_case;
+use markdown::{self, Heading};
 use rayon::prelude::*;
-use std::collections::BTreeMap;
+use site::Site;
+use std::collections::{BTreeMap, BTreeSet};
 use std::ffi::OsStr;
 use std::fs::{self, File};
 use std::io::BufReader;
 use std::path::{Path, PathBuf};
-use templates::LinkLabel;
-use util;
+use std::slice;
+use std::sync::Mutex;
+use templates::{
+    ArchiveMonth, ArchiveYear, BlogContext, LinkLabel, Pagination, PostLink, SectionLink, Series,
+    SeriesPart, Tag, TagCount,
+};
+use util::{self, HtmlOptions};
 
 const CSS: &'static str = "css";
 const HTML: &'static str = "html";
@@ -18,21 +30,35 @@ const JPG: &'static str = "jpg";
 const JS: &'static str = "js";
 const MD: &'static str = "md";
 const SASS: &'static str = "sass";
+const INDEX: &'static str = "index";
 const BLOG_DIR: &'static str = "blog";
+const TAGS_DIR: &'static str = "tags";
+const SERIES_DIR: &'static str = "series";
+const ARCHIVE_DIR: &'static str = "archive";
+const AUTHORS_DIR: &'static str = "authors";
+const REDIRECTS_FILE: &'static str = "_redirects";
+const RELATED_POSTS: usize = 3;
+const PAGE_DIR: &'static str = "page";
+const PAGES_SECTION: &'static str = "pages";
+const PAGE_TYPE: &'static str = "page";
 const IMAGE_DIR: &'static str = "image";
 const THUMBNAIL_DIR: &'static str = "thumbnail";
 
 use templates::{
-    AboutTemplate, Blog, BlogTemplate, GalleryTemplate, IndexTemplate, NotFoundTemplate,
+    ArchiveTemplate, AuthorTemplate, Blog, BlogTemplate, GalleryTemplate, IndexTemplate,
+    PageTemplate, RedirectTemplate, SectionTemplate, SeriesTemplate, TagTemplate, TagsTemplate,
 };
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum ResourceType {
     Blog,
     Icon,
+    Page,
     Photo,
     Style,
     Script,
+    /// Any file living alongside a post in a page bundle, copied as is
+    Asset,
 }
 
 impl ResourceType {
@@ -51,96 +77,301 @@ impl ResourceType {
     }
 }
 
+/// A file to read as a resource
+#[derive(Debug)]
+struct Source {
+    path: PathBuf,
+    /// The path relative to the static directory, which no other resource shares
+    key: String,
+}
+
+impl Source {
+    fn new(path: PathBuf, dir: &str, file_name: &str) -> Self {
+        Source {
+            path,
+            key: join_link(&[dir, file_name]),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct SiteResource {
     timing: Timing,
     changed: bool,
     path: PathBuf,
+    key: String,
     name: String,
+    section: String,
     resource_type: ResourceType,
+    front_matter: FrontMatter,
+    summary: String,
+    word_count: usize,
+    author: Author,
+    published: bool,
+    /// Whether the post is the `index.md` of a page bundle, rendered into its own directory
+    bundle: bool,
+    /// Whether the content hash goes in the output's file name
+    fingerprinted: bool,
 }
 
 impl SiteResource {
-    fn as_blog(&self) -> Blog {
-        Blog::new(
-            format!("{}/{}.{}", BLOG_DIR, self.name, HTML),
-            to_title_case(&self.name),
-            self.timing.created.to_rfc3339(),
+    /// Unique name of the resource, used as its key in the metadata file
+    fn key(&self) -> String {
+        self.key.clone()
+    }
+
+    /// Output location relative to the build directory, nested under the resource's section
+    fn link_in(&self, dir: &str, ext: &str) -> String {
+        let file_name = format!("{}.{}", self.name, ext);
+        join_link(&[dir, self.section.as_str(), file_name.as_str()])
+    }
+
+    fn blog_link(&self) -> String {
+        if self.bundle {
+            return format!("{}/{}.{}", self.bundle_dir(), INDEX, HTML);
+        }
+        // top level blogs stay in the blog dir, sectioned blogs live under their section
+        let dir = if self.section.is_empty() { BLOG_DIR } else { "" };
+        self.link_in(dir, HTML)
+    }
+
+    /// Pages always render to the top level, regardless of section
+    fn page_link(&self) -> String {
+        format!("{}.{}", self.name, HTML)
+    }
+
+    fn bundle_dir(&self) -> String {
+        bundle_dir(&self.resource_type, &self.section, &self.name)
+    }
+
+    /// Where relative links in the markdown point, empty unless the post is a page bundle
+    fn bundle_base(&self) -> String {
+        if self.bundle {
+            format!("/{}", self.bundle_dir())
+        } else {
+            String::new()
+        }
+    }
+
+    /// Output location of a style or script, with part of the content hash in the file name
+    /// when fingerprinted
+    fn static_link(&self, ext: &str) -> String {
+        if self.fingerprinted {
+            let file_name = format!("{}.{}.{}", self.name, &self.timing.hash[..8], ext);
+            join_link(&[self.section.as_str(), file_name.as_str()])
+        } else {
+            self.link_in("", ext)
+        }
+    }
+
+    /// Assets keep their file name, and their section is the directory of their bundle
+    fn asset_link(&self) -> String {
+        join_link(&[self.section.as_str(), self.name.as_str()])
+    }
+
+    fn in_section(&self, section: &str) -> bool {
+        self.section == section || self.section.starts_with(&format!("{}/", section))
+    }
+
+    fn as_link_label(&self) -> LinkLabel {
+        LinkLabel::new(
+            format!("/{}", self.link_in(THUMBNAIL_DIR, JPG)),
+            format!("/{}", self.link_in(IMAGE_DIR, JPG)),
+            self.name.clone(),
         )
     }
 
-    fn path_exists(&self, build_dir: &Path) -> bool {
+    fn title(&self) -> String {
+        self.front_matter
+            .title
+            .clone()
+            .unwrap_or_else(|| to_title_case(&self.name))
+    }
+
+    fn as_post_link(&self) -> PostLink {
+        PostLink::new(self.title(), format!("/{}", self.blog_link()))
+    }
+
+    fn as_blog(&self) -> Blog {
+        let front_matter = &self.front_matter;
+        Blog {
+            link: format!("/{}", self.blog_link()),
+            title: self.title(),
+            created: self.created().to_rfc3339(),
+            updated: front_matter.updated.map(|updated| updated.to_rfc3339()),
+            description: front_matter.description.clone(),
+            image: front_matter
+                .image
+                .as_ref()
+                .map(|image| markdown::rebase_url(image, &self.bundle_base())),
+            tags: self.tags().iter().map(|(_, tag)| as_tag(tag)).collect(),
+            summary: self.summary.clone(),
+            word_count: self.word_count,
+            reading_time: markdown::reading_time(self.word_count),
+            author_name: self.author.name.clone(),
+            author_link: author_link(&self.author.id),
+        }
+    }
+
+    /// Front matter tags keyed and deduplicated by their slug
+    fn tags(&self) -> BTreeMap<String, &str> {
+        self.front_matter
+            .tags
+            .iter()
+            .map(|tag| (tag_slug(tag), tag.as_str()))
+            .collect()
+    }
+
+    /// Words in the title that are long enough to say something about the post
+    fn title_words(&self) -> BTreeSet<String> {
+        self.title()
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| word.len() > 3)
+            .map(|word| word.to_lowercase())
+            .collect()
+    }
+
+    /// Other posts ranked by shared tags, then by shared title words, then by recency
+    fn related<'a>(&self, candidates: &[&'a SiteResource]) -> Vec<&'a SiteResource> {
+        let tags = self.tags();
+        let words = self.title_words();
+        let mut scored = candidates
+            .iter()
+            .filter(|r| r.key() != self.key())
+            .map(|r| {
+                let shared_tags = r.tags().keys().filter(|t| tags.contains_key(*t)).count();
+                let shared_words = r.title_words().intersection(&words).count();
+                ((shared_tags, shared_words, r.created()), *r)
+            }).filter(|&((shared_tags, shared_words, _), _)| shared_tags + shared_words > 0)
+            .collect::<Vec<_>>();
+        scored.sort_by(|a, b| b.0.cmp(&a.0));
+        scored
+            .into_iter()
+            .take(RELATED_POSTS)
+            .map(|(_, r)| r)
+            .collect()
+    }
+
+    /// The front matter date takes precedence over the filesystem timing
+    fn created(&self) -> DateTime<Utc> {
+        self.front_matter.date.unwrap_or(self.timing.created)
+    }
+
+    /// Files the resource builds into, relative to the build directory, including the redirect
+    /// pages of its aliases
+    fn outputs(&self) -> Vec<String> {
+        let aliases = self.front_matter.aliases.iter().map(|alias| alias_file(alias));
         match self.resource_type {
-            ResourceType::Blog => build_dir
-                .join(BLOG_DIR)
-                .join(&self.name)
-                .with_extension(HTML)
-                .exists(),
-            ResourceType::Script => build_dir.join(&self.name).with_extension(JS).exists(),
-            ResourceType::Style => build_dir.join(&self.name).with_extension(CSS).exists(),
-            ResourceType::Photo => {
-                build_dir
-                    .join(IMAGE_DIR)
-                    .join(&self.name)
-                    .with_extension(JPG)
-                    .exists()
-                    && build_dir
-                        .join(THUMBNAIL_DIR)
-                        .join(&self.name)
-                        .with_extension(JPG)
-                        .exists()
-            }
-            ResourceType::Icon => build_dir.join(&self.name).with_extension(ICO).exists(),
+            ResourceType::Blog => Some(self.blog_link()).into_iter().chain(aliases).collect(),
+            ResourceType::Page => Some(self.page_link()).into_iter().chain(aliases).collect(),
+            ResourceType::Script => vec![self.static_link(JS)],
+            ResourceType::Style => vec![self.static_link(CSS)],
+            ResourceType::Photo => vec![
+                self.link_in(IMAGE_DIR, JPG),
+                self.link_in(THUMBNAIL_DIR, JPG),
+            ],
+            ResourceType::Icon => vec![self.link_in("", ICO)],
+            ResourceType::Asset => vec![self.asset_link()],
         }
     }
 
-    fn write_resource(&self, build_dir: &Path) -> OResult<()> {
+    fn path_exists(&self, build_dir: &Path) -> bool {
+        self.outputs()
+            .iter()
+            .all(|output| build_dir.join(output).exists())
+    }
+
+    fn write_resource(&self, build_dir: &Path, site: &Site, context: &BlogContext) -> OResult<()> {
         match self.resource_type {
-            ResourceType::Blog => self.write_blog(build_dir),
+            ResourceType::Blog => self.write_blog(build_dir, site, context),
+            ResourceType::Page => self.write_page(build_dir, site),
             ResourceType::Script => self.copy_resource(build_dir, JS), //TODO: minify
-            ResourceType::Style => self.write_style(build_dir),
+            ResourceType::Style => self.write_style(build_dir, site),
             ResourceType::Photo => self.write_photo(build_dir),
             ResourceType::Icon => self.copy_resource(build_dir, ICO),
+            ResourceType::Asset => self.copy_asset(build_dir),
         }
     }
 
     fn copy_resource(&self, build_dir: &Path, ext: &str) -> OResult<()> {
-        let out_file = &build_dir.join(&self.name).with_extension(ext);
+        let out_file = &build_dir.join(self.static_link(ext));
         info!("Copying resource to {:?}", out_file);
         util::write_file(out_file, util::read_bytes(&self.path)?)
     }
 
-    fn write_style(&self, build_dir: &Path) -> OResult<()> {
+    fn copy_asset(&self, build_dir: &Path) -> OResult<()> {
+        let out_file = &build_dir.join(self.asset_link());
+        info!("Copying bundle asset to {:?}", out_file);
+        util::write_file(out_file, util::read_bytes(&self.path)?)
+    }
+
+    fn write_style(&self, build_dir: &Path, site: &Site) -> OResult<()> {
         use super::sass_rs::*;
         let mut options = Options::default();
-        options.output_style = OutputStyle::Compressed;
+        options.output_style = if site.profile.minify {
+            OutputStyle::Compressed
+        } else {
+            OutputStyle::Expanded
+        };
         info!("Reading style file from {:?}", &self.path);
         let sass = compile_file(&self.path, options).map_err(OpaqueError::new)?;
-        let css_file = build_dir.join(&self.name).with_extension(CSS);
+        let css_file = build_dir.join(self.static_link(CSS));
         info!("Building style file {} to {:?}", self.name, css_file);
         util::write_file(css_file, sass)
     }
 
-    fn write_blog(&self, build_dir: &Path) -> OResult<()> {
-        use super::pulldown_cmark::*;
-        let opts = {
-            let mut opts = Options::empty();
-            opts.insert(OPTION_ENABLE_FOOTNOTES);
-            opts.insert(OPTION_ENABLE_TABLES);
-            opts
-        };
-        info!("Reading blog from {:?}", self.path);
+    /// Renders the post, keeping its heading ids clear of the `reserved` ids of its template
+    fn read_markdown(&self, reserved: &[&str]) -> OResult<(String, Vec<Heading>)> {
         let buf = util::read_file(&self.path)?;
-        let markdown_parser = Parser::new_ext(&buf, opts);
-        let mut html_buf = String::new();
-        html::push_html(&mut html_buf, markdown_parser);
-        let blog = BlogTemplate::new(&html_buf, self.as_blog());
-        let blog_file = build_dir
-            .join(BLOG_DIR)
-            .join(&self.name)
-            .with_extension(HTML);
+        let (_, markdown) = front_matter::split(&buf)?;
+        Ok(markdown::render_rebased(markdown, &self.bundle_base(), reserved))
+    }
+
+    fn write_blog(&self, build_dir: &Path, site: &Site, context: &BlogContext) -> OResult<()> {
+        info!("Reading blog from {:?}", self.path);
+        let (html_buf, headings) = self.read_markdown(&[])?;
+        let toc = if self.front_matter.toc {
+            markdown::toc(&headings)
+        } else {
+            vec![]
+        };
+        let blog = BlogTemplate::new(site, &html_buf, self.as_blog(), context.clone(), toc);
+        let blog_file = build_dir.join(self.blog_link());
         info!("Writing blog file {} to {:?}", self.name, blog_file);
-        util::write_minified_html(blog_file, blog.render()?)?;
+        write_html(site, build_dir, &blog_file, blog.render()?)?;
+        Ok(())
+    }
+
+    fn write_page(&self, build_dir: &Path, site: &Site) -> OResult<()> {
+        info!("Reading page from {:?}", self.path);
+        let front_matter = &self.front_matter;
+        let title = self.title();
+        let data_sets = front_matter
+            .data
+            .iter()
+            .map(|name| {
+                site.data.get(name).ok_or_else(|| {
+                    OpaqueError::new(format!("Unknown data set {} in {:?}", name, self.path))
+                })
+            }).collect::<OResult<Vec<_>>>()?;
+        // the page and each of its data sets have an id of their own
+        let reserved = Some(self.name.as_str())
+            .into_iter()
+            .chain(data_sets.iter().map(|set| set.name.as_str()))
+            .collect::<Vec<_>>();
+        let (html_buf, _) = self.read_markdown(&reserved)?;
+        let page = PageTemplate::new(
+            site,
+            &html_buf,
+            &self.name,
+            &title,
+            front_matter.subtitle.as_ref().map_or("", |s| s.as_str()),
+            front_matter.description.as_ref().unwrap_or(&title),
+            data_sets,
+        );
+        let page_file = build_dir.join(self.page_link());
+        info!("Writing page file {} to {:?}", self.name, page_file);
+        write_html(site, build_dir, &page_file, page.render()?)?;
         Ok(())
     }
 
@@ -148,37 +379,205 @@ impl SiteResource {
         use image::*;
         info!("Reading photo from {:?}", self.path);
         let image = load(BufReader::new(File::open(&self.path)?), ImageFormat::JPEG)?;
-        let thumbnail_path = build_dir
-            .join(THUMBNAIL_DIR)
-            .join(&self.name)
-            .with_extension(JPG);
+        let thumbnail_path = build_dir.join(self.link_in(THUMBNAIL_DIR, JPG));
         let thumbnail = image.resize(640, 360, FilterType::Triangle);
         info!("Building photo thumbnail to {:?}", thumbnail_path);
+        util::create_parent_dirs(&thumbnail_path)?;
         thumbnail.save(&thumbnail_path)?;
-        let fullsize_path = build_dir
-            .join(IMAGE_DIR)
-            .join(&self.name)
-            .with_extension(JPG);
+        let fullsize_path = build_dir.join(self.link_in(IMAGE_DIR, JPG));
         let fullsize = image.resize(1280, 720, FilterType::Triangle);
         info!("Building fullsize photo to {:?}", fullsize_path);
+        util::create_parent_dirs(&fullsize_path)?;
         fullsize.save(&fullsize_path)?;
         Ok(())
     }
 }
 
+/// Choices about which resources get read and where their dates come from
+#[derive(Debug, Default)]
+pub struct ReadOptions {
+    /// Keeps drafts and scheduled posts in the build, for previewing
+    pub include_drafts: bool,
+    /// Commit history that takes precedence over the metadata file for created and modified
+    pub git_dates: Option<GitDates>,
+}
+
 #[derive(Debug)]
 pub struct SiteResources {
     resources: Vec<SiteResource>,
+    site: Site,
+    include_drafts: bool,
+    /// Whether posts were published, unpublished or deleted since the last build
+    listing_changed: bool,
+    /// Whether the site, authors or data files changed since the last build
+    site_changed: bool,
+    /// Files the last build wrote that don't belong to any one resource
+    generated: Mutex<BTreeSet<String>>,
 }
 
 impl SiteResources {
-    pub fn read_resources(static_dir: &Path, config: &Config) -> OResult<Self> {
+    pub fn read_resources(
+        static_dir: &Path,
+        config: &Config,
+        site: &Site,
+        options: &ReadOptions,
+    ) -> OResult<Self> {
         let mut resources = vec![];
 
         info!("Reading resources from static directory {:?}", static_dir);
-        for entry in fs::read_dir(static_dir)? {
-            let entry = entry?;
-            let path = entry.path();
+        Self::read_section(static_dir, "", config, &site.authors, &mut resources)?;
+        if let Some(ref git_dates) = options.git_dates {
+            Self::read_commit_dates(git_dates, &mut resources);
+        }
+        Self::check_outputs(&resources)?;
+        let mut site = site.clone();
+        if site.profile.fingerprint {
+            for resource in resources.iter_mut() {
+                let ext = match resource.resource_type {
+                    ResourceType::Style => CSS,
+                    ResourceType::Script => JS,
+                    _ => continue,
+                };
+                resource.fingerprinted = true;
+                site.fingerprints.insert(
+                    format!("/{}", resource.link_in("", ext)),
+                    format!("/{}", resource.static_link(ext)),
+                );
+            }
+        }
+        // sort newest to oldest
+        resources.sort_unstable_by_key(|r| r.created());
+        resources.reverse();
+        let site_changed = config.site_hash != site.hash();
+        if site_changed {
+            info!("Site files or profile changed, rebuilding every page");
+        }
+        let mut site_resources = SiteResources {
+            resources,
+            site,
+            include_drafts: options.include_drafts,
+            listing_changed: false,
+            site_changed,
+            generated: Mutex::new(BTreeSet::new()),
+        };
+        let prev_listed = config
+            .resources
+            .iter()
+            .filter(|&(_, meta)| meta.listed)
+            .map(|(key, _)| key.clone())
+            .collect::<BTreeSet<_>>();
+        site_resources.listing_changed = site_resources.listed_keys() != prev_listed;
+        Ok(site_resources)
+    }
+
+    /// Takes dates from the commit history for resources that don't have them from the
+    /// metadata file. The history is only read when there's a resource that needs it.
+    fn read_commit_dates(git_dates: &GitDates, resources: &mut [SiteResource]) {
+        let mut pending = resources
+            .iter_mut()
+            .filter(|r| r.changed || !r.timing.from_git)
+            .peekable();
+        if pending.peek().is_none() {
+            return;
+        }
+        let history = match git_dates.history() {
+            Ok(history) => history,
+            Err(e) => {
+                warn!("Could not read the commit history: {}", e);
+                return;
+            }
+        };
+        for resource in pending {
+            // uncommitted files keep the timing from the metadata file, and files with
+            // uncommitted edits keep their modified date from it too
+            let timing = &mut resource.timing;
+            timing.from_git = false;
+            match history.get(&resource.path) {
+                Ok(Some(dates)) => {
+                    timing.created = dates.created;
+                    if let Some(modified) = dates.modified {
+                        timing.modified = modified;
+                        timing.from_git = true;
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => warn!("Could not read commit dates of {:?}: {}", resource.path, e),
+            }
+        }
+    }
+
+    /// Fails when two resources share a metadata key or build into the same file, or one builds
+    /// into a page the site generates itself
+    fn check_outputs(resources: &[SiteResource]) -> OResult<()> {
+        let mut keys: BTreeMap<&str, &Path> = BTreeMap::new();
+        for resource in resources {
+            if let Some(other) = keys.insert(&resource.key, &resource.path) {
+                return Err(OpaqueError::new(format!(
+                    "{:?} and {:?} both have the metadata key {}",
+                    other, resource.path, resource.key
+                )));
+            }
+        }
+        let mut section_pages = BTreeSet::new();
+        for resource in resources.iter().filter(|r| match r.resource_type {
+            ResourceType::Page | ResourceType::Asset => false,
+            _ => true,
+        }) {
+            let mut section = resource.section.as_str();
+            while !section.is_empty() {
+                section_pages.insert(format!("{}/{}.{}", section, INDEX, HTML));
+                section = parent_section(section);
+            }
+        }
+        let mut owners: BTreeMap<String, &Path> = BTreeMap::new();
+        for resource in resources {
+            for output in resource.outputs() {
+                if is_generated(&output) || section_pages.contains(&output) {
+                    return Err(OpaqueError::new(format!(
+                        "{:?} builds into {}, which is a generated page",
+                        resource.path, output
+                    )));
+                }
+                if let Some(other) = owners.insert(output.clone(), &resource.path) {
+                    return Err(OpaqueError::new(format!(
+                        "{:?} and {:?} both build into {}",
+                        other, resource.path, output
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads every resource in `dir`, recursing into subdirectories as nested sections. A
+    /// subdirectory with an `index.md` is a page bundle rather than a section.
+    fn read_section(
+        dir: &Path,
+        section: &str,
+        config: &Config,
+        authors: &Authors,
+        resources: &mut Vec<SiteResource>,
+    ) -> OResult<()> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let file_name = file_name(&path)?;
+
+            if file_name.starts_with('.') {
+                continue;
+            }
+
+            if path.is_dir() {
+                if is_bundle(&path)? {
+                    info!("Reading page bundle {} from {:?}", file_name, path);
+                    Self::read_bundle(&path, &file_name, section, config, authors, resources)?;
+                } else {
+                    let subsection = join_link(&[section, file_name.as_str()]);
+                    info!("Reading section {} from {:?}", subsection, path);
+                    Self::read_section(&path, &subsection, config, authors, resources)?;
+                }
+                continue;
+            }
+
             let extension = path
                 .extension()
                 .and_then(OsStr::to_str)
@@ -193,11 +592,7 @@ impl SiteResources {
                 Ok(t) => t,
             };
 
-            let name = path
-                .file_name()
-                .ok_or(OpaqueError::new("Path ending in ...!"))?
-                .to_str()
-                .ok_or(OpaqueError::new(format!("Invalid filename: {:?}", &path)))?
+            let name = file_name
                 .trim_right_matches(&format!(".{}", extension))
                 .to_owned();
 
@@ -205,44 +600,246 @@ impl SiteResources {
                 continue;
             }
 
-            let metadata = entry.metadata()?;
-            let prev = config.timings.get(&name);
-            let timing = Timing::from_metadata_and_prev(&metadata, prev)?;
-            let changed = prev
-                .map(|prev_timing| prev_timing != &timing)
-                .unwrap_or(true);
-
-            resources.push(SiteResource {
-                timing,
-                changed,
-                path,
-                name,
-                resource_type,
-            })
+            let source = Source::new(path, section, &file_name);
+            let resource =
+                Self::read_resource(source, name, section, resource_type, false, config, authors)?;
+            resources.push(resource);
         }
-        // sort newest to oldest
-        resources.sort_unstable_by_key(|r| r.timing.created);
-        resources.reverse();
-        Ok(SiteResources { resources })
+        Ok(())
     }
 
-    pub fn timings(&self) -> BTreeMap<String, Timing> {
+    /// Reads a post living in `dir/index.md`, and every other file under `dir` as an asset to
+    /// be copied next to it, keeping its path within the bundle
+    fn read_bundle(
+        dir: &Path,
+        name: &str,
+        section: &str,
+        config: &Config,
+        authors: &Authors,
+        resources: &mut Vec<SiteResource>,
+    ) -> OResult<()> {
+        let index_name = format!("{}.{}", INDEX, MD);
+        let index = dir.join(&index_name);
+        let source_dir = join_link(&[section, name]);
+        let post = Self::read_resource(
+            Source::new(index.clone(), &source_dir, &index_name),
+            name.to_owned(),
+            section,
+            ResourceType::Blog,
+            true,
+            config,
+            authors,
+        )?;
+        let bundle_dir = post.bundle_dir();
+        let mut files = vec![];
+        bundle_files(dir, "", &mut files)?;
+        for (path, file_name) in files.into_iter().filter(|&(ref path, _)| *path != index) {
+            let mut asset = Self::read_resource(
+                Source::new(path, &source_dir, &file_name),
+                file_name,
+                &bundle_dir,
+                ResourceType::Asset,
+                false,
+                config,
+                authors,
+            )?;
+            // assets go out with their post
+            asset.published = post.published;
+            resources.push(asset);
+        }
+        resources.push(post);
+        Ok(())
+    }
+
+    fn read_resource(
+        source: Source,
+        name: String,
+        section: &str,
+        resource_type: ResourceType,
+        bundle: bool,
+        config: &Config,
+        authors: &Authors,
+    ) -> OResult<SiteResource> {
+        let Source { path, key } = source;
+        let metadata = fs::metadata(&path)?;
+        let contents = util::read_bytes(&path)?;
+        // older metadata files keyed resources by section and name
+        let prev = config
+            .get(&key, &join_link(&[section, name.as_str()]))
+            .map(|meta| meta.timing());
+        let timing = Timing::from_contents_and_prev(&metadata, &contents, prev.as_ref())?;
+        let changed = prev
+            .map(|prev_timing| prev_timing.hash != timing.hash)
+            .unwrap_or(true);
+
+        let buf = match resource_type {
+            ResourceType::Blog => String::from_utf8(contents)?,
+            _ => String::new(),
+        };
+        let (front_matter, markdown) = match resource_type {
+            ResourceType::Blog => front_matter::split(&buf).map_err(|e| {
+                OpaqueError::new(format!("Invalid front matter in {:?}: {}", &path, e))
+            })?,
+            _ => (FrontMatter::default(), ""),
+        };
+        let is_page = section == PAGES_SECTION
+            || front_matter.kind.as_ref().map_or(false, |kind| kind == PAGE_TYPE);
+        let resource_type = match resource_type {
+            ResourceType::Blog if is_page => ResourceType::Page,
+            t => t,
+        };
+        // drafts and posts scheduled for the future stay out of the build
+        let published = !front_matter.draft
+            && front_matter.date.map(|date| date <= Utc::now()).unwrap_or(true);
+        if !published {
+            info!("Found unpublished resource {:?}", &path);
+        }
+        if let Some(alias) = front_matter
+            .aliases
+            .iter()
+            .find(|alias| alias.split('/').any(|part| part == ".."))
+        {
+            return Err(OpaqueError::new(format!(
+                "Alias {} in {:?} leaves the build directory",
+                alias, &path
+            )));
+        }
+        if let Some(tag) = front_matter.tags.iter().find(|tag| tag_slug(tag).is_empty()) {
+            return Err(OpaqueError::new(format!(
+                "Tag {:?} in {:?} needs a letter or number to link to",
+                tag, &path
+            )));
+        }
+        let author = authors
+            .get(front_matter.author.as_ref().map(|s| s.as_str()))
+            .map_err(|e| OpaqueError::new(format!("{} in {:?}", e, &path)))?
+            .clone();
+
+        // bundled pages render away from their assets, and summaries away from the post
+        let base = if bundle {
+            format!("/{}", bundle_dir(&resource_type, section, &name))
+        } else {
+            String::new()
+        };
+
+        Ok(SiteResource {
+            timing,
+            changed,
+            path,
+            key,
+            name,
+            section: section.to_owned(),
+            resource_type,
+            front_matter,
+            summary: markdown::summarize(markdown, &base),
+            word_count: markdown::word_count(markdown),
+            author,
+            published,
+            bundle,
+            fingerprinted: false,
+        })
+    }
+
+    /// Metadata to keep for the next build, which leaves out resources that have since been
+    /// deleted. Anything in `prev` that isn't built anymore is removed from the build directory.
+    pub fn metadata(&self, build_dir: &Path, prev: &Config) -> OResult<Config> {
+        let mut current = Config::new(
+            self.resources
+                .iter()
+                .map(|r| {
+                    let meta = ResourceMeta::new(&r.timing, r.outputs(), self.is_listed(r));
+                    (r.key(), meta)
+                }).collect(),
+        );
+        current.site_hash = self.site.hash();
+        current.generated = self.generated.lock().unwrap().clone();
+        let current_outputs = self
+            .resources
+            .iter()
+            .flat_map(|r| r.outputs())
+            .chain(current.generated.iter().cloned())
+            .collect::<BTreeSet<_>>();
+        let prev_outputs = prev
+            .all_resources()
+            .flat_map(|meta| meta.outputs.iter())
+            .chain(prev.generated.iter());
+        for output in prev_outputs {
+            let out_file = build_dir.join(output);
+            // deleted resources, old outputs of the ones still around, and listing pages that
+            // aren't needed anymore
+            if !current_outputs.contains(output) {
+                util::remove_output(&out_file)?;
+            }
+        }
+        Ok(current)
+    }
+
+    fn is_visible(&self, resource: &SiteResource) -> bool {
+        resource.published || self.include_drafts
+    }
+
+    /// Resources that should appear in the build
+    fn visible(&self) -> impl Iterator<Item = &SiteResource> {
+        self.resources.iter().filter(move |r| self.is_visible(r))
+    }
+
+    /// Posts in the build, which other posts can link to
+    fn is_listed(&self, resource: &SiteResource) -> bool {
+        resource.resource_type == ResourceType::Blog && self.is_visible(resource)
+    }
+
+    fn listed_keys(&self) -> BTreeSet<String> {
         self.resources
             .iter()
-            .map(|r| (r.name.clone(), r.timing.clone()))
+            .filter(|r| self.is_listed(r))
+            .map(|r| r.key())
             .collect()
     }
 
-    pub fn build_all(&self, build_dir: &Path, ignore_changed: bool) -> OResult<()> {
+    /// Every section containing a resource, along with all of its parent sections
+    fn sections(&self) -> BTreeSet<String> {
+        let mut sections = BTreeSet::new();
+        for resource in self
+            .visible()
+            .filter(|r| match r.resource_type {
+                ResourceType::Page | ResourceType::Asset => false,
+                _ => true,
+            }) {
+            let mut section = resource.section.as_str();
+            while !section.is_empty() {
+                sections.insert(section.to_owned());
+                section = parent_section(section);
+            }
+        }
+        sections
+    }
+
+    pub fn build_all(
+        &self,
+        build_dir: &Path,
+        ignore_changed: bool,
+        page_size: usize,
+    ) -> OResult<()> {
         Self::create_dir_if_not_exists(build_dir)?;
         Self::create_dir_if_not_exists(&build_dir.join(BLOG_DIR))?;
         Self::create_dir_if_not_exists(&build_dir.join(IMAGE_DIR))?;
         Self::create_dir_if_not_exists(&build_dir.join(THUMBNAIL_DIR))?;
         info!("Writing resources into build directory {:?}", build_dir);
         self.write_gallery(build_dir)?;
-        self.write_index(build_dir)?;
-        self.write_static_templates(build_dir)?;
+        self.write_index(build_dir, page_size)?;
+        self.write_sections(build_dir, page_size)?;
+        self.write_tags(build_dir, page_size)?;
+        self.write_series(build_dir)?;
+        self.write_archive(build_dir)?;
+        self.write_authors(build_dir, page_size)?;
+        self.write_default_pages(build_dir)?;
+        self.write_redirects(build_dir)?;
         self.write_resources(build_dir, ignore_changed)?;
+        if self.site.profile.compress {
+            util::gzip_files(build_dir, &[HTML, CSS, JS])?;
+        } else {
+            util::remove_gzip_files(build_dir, &[HTML, CSS, JS])?;
+        }
         info!("Done");
         Ok(())
     }
@@ -256,56 +853,708 @@ impl SiteResources {
     }
 
     fn write_resources(&self, build_dir: &Path, ignore_changed: bool) -> OResult<()> {
+        self.remove_unpublished(build_dir)?;
+        let contexts = self.blog_contexts();
+        let no_context = BlogContext::default();
+        // blogs link to each other, so a change to any of them, or to which of them get built,
+        // means rebuilding all of them
+        let blogs_changed =
+            self.listing_changed || self.resources.iter().any(|r| self.is_listed(r) && r.changed);
+        // pages link to fingerprinted styles and scripts by the hash of their contents
+        let fingerprints_changed = self.visible().any(|r| r.fingerprinted && r.changed);
         self.resources
             .par_iter()
-            .filter(|r| r.changed || ignore_changed || !r.path_exists(build_dir))
-            .map(|r| r.write_resource(build_dir))
+            .filter(|r| self.is_visible(r))
+            .filter(|r| {
+                r.changed
+                    || ignore_changed
+                    || self.site_changed
+                    || !r.path_exists(build_dir)
+                    || (blogs_changed && r.resource_type == ResourceType::Blog)
+                    || (fingerprints_changed
+                        && (r.resource_type == ResourceType::Blog
+                            || r.resource_type == ResourceType::Page))
+            }).map(|r| {
+                let context = contexts.get(&r.key()).unwrap_or(&no_context);
+                r.write_resource(build_dir, &self.site, context)
+            })
             .collect()
     }
 
-    fn write_gallery(&self, build_dir: &Path) -> OResult<()> {
-        let all_photos = self
+    /// Cross-resource pass computing what each blog needs to know about the others
+    fn blog_contexts(&self) -> BTreeMap<String, BlogContext> {
+        let mut contexts: BTreeMap<String, BlogContext> = self
+            .visible()
+            .filter(|r| r.resource_type == ResourceType::Blog)
+            .map(|r| (r.key(), BlogContext::default()))
+            .collect();
+        for (slug, (name, parts)) in self.series() {
+            let total = parts.len();
+            for (i, part) in parts.iter().enumerate() {
+                let series_parts = parts
+                    .iter()
+                    .enumerate()
+                    .map(|(j, p)| SeriesPart::new(p.as_post_link(), i == j))
+                    .collect();
+                let series = Series::new(
+                    name.to_owned(),
+                    series_link(&slug),
+                    i + 1,
+                    total,
+                    series_parts,
+                );
+                if let Some(context) = contexts.get_mut(&part.key()) {
+                    context.series = Some(series);
+                }
+            }
+        }
+        // drafts never show up as a neighbour, even when previewing
+        let published = self
             .resources
             .iter()
-            .filter(|r| r.resource_type == ResourceType::Photo)
-            .map(|r| {
-                LinkLabel::new(
-                    format!("{}/{}.{}", THUMBNAIL_DIR, r.name, JPG),
-                    format!("{}/{}.{}", IMAGE_DIR, r.name, JPG),
-                    r.name.clone(),
+            .filter(|r| r.resource_type == ResourceType::Blog && r.published)
+            .collect::<Vec<_>>();
+        for (i, blog) in published.iter().enumerate() {
+            if let Some(context) = contexts.get_mut(&blog.key()) {
+                // resources are sorted newest to oldest
+                context.previous = published.get(i + 1).map(|r| r.as_post_link());
+                context.next = i.checked_sub(1).map(|j| published[j].as_post_link());
+            }
+        }
+        for (key, context) in contexts.iter_mut() {
+            if let Some(blog) = self.resources.iter().find(|r| &r.key() == key) {
+                context.related = blog
+                    .related(&published[..])
+                    .into_iter()
+                    .map(|r| r.as_blog())
+                    .collect();
+            }
+        }
+        contexts
+    }
+
+    /// Blogs grouped into their series by slug, in reading order
+    fn series(&self) -> BTreeMap<String, (&str, Vec<&SiteResource>)> {
+        let mut series: BTreeMap<String, (&str, Vec<&SiteResource>)> = BTreeMap::new();
+        for resource in self
+            .visible()
+            .filter(|r| r.resource_type == ResourceType::Blog)
+        {
+            if let Some(ref name) = resource.front_matter.series {
+                series
+                    .entry(to_kebab_case(name))
+                    .or_insert_with(|| (name.as_str(), vec![]))
+                    .1
+                    .push(resource);
+            }
+        }
+        for &mut (_, ref mut parts) in series.values_mut() {
+            // explicitly ordered parts come first, the rest follow in publishing order
+            parts.sort_by_key(|r| {
+                (
+                    r.front_matter.series_part.is_none(),
+                    r.front_matter.series_part,
+                    r.created(),
                 )
-            }).collect::<Vec<_>>();
-        let gallery = GalleryTemplate::new(&all_photos[..]);
+            });
+        }
+        series
+    }
+
+    /// Cleans up output left behind by a post that went back to being a draft
+    fn remove_unpublished(&self, build_dir: &Path) -> OResult<()> {
+        for resource in self.resources.iter().filter(|r| !self.is_visible(r)) {
+            for output in resource.outputs() {
+                util::remove_output(&build_dir.join(output))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_gallery(&self, build_dir: &Path) -> OResult<()> {
+        let all_photos = self
+            .visible()
+            .filter(|r| r.resource_type == ResourceType::Photo)
+            .map(|r| r.as_link_label())
+            .collect::<Vec<_>>();
+        let gallery = GalleryTemplate::new(&self.site, &all_photos[..]);
         let gallery_path = build_dir.join("gallery.html");
         info!("Writing gallery file to {:?}", gallery_path);
-        util::write_minified_html(gallery_path, gallery.render()?)?;
+        self.write_generated(build_dir, &gallery_path, gallery.render()?)?;
         Ok(())
     }
 
-    fn write_index(&self, build_dir: &Path) -> OResult<()> {
-        let all_blogs = self
-            .resources
-            .iter()
+    fn write_index(&self, build_dir: &Path, page_size: usize) -> OResult<()> {
+        let mut blog_resources = self
+            .visible()
             .filter(|r| r.resource_type == ResourceType::Blog)
+            .collect::<Vec<_>>();
+        // stable, so everything that isn't pinned stays newest to oldest
+        blog_resources.sort_by_key(|r| {
+            let front_matter = &r.front_matter;
+            (!front_matter.pinned, if front_matter.pinned { front_matter.weight } else { 0 })
+        });
+        let all_blogs = blog_resources
+            .iter()
+            .map(|r| r.as_blog())
+            .collect::<Vec<_>>();
+        let featured = blog_resources
+            .iter()
+            .filter(|r| r.front_matter.featured)
             .map(|r| r.as_blog())
             .collect::<Vec<_>>();
-        let index = IndexTemplate::new(&all_blogs[..]);
-        let index_path = build_dir.join("index.html");
-        info!("Writing index file to {:?}", index_path);
-        util::write_minified_html(index_path, index.render()?)?;
+        let index_link = format!("/{}.{}", INDEX, HTML);
+        self.write_pages(
+            build_dir,
+            &all_blogs[..],
+            page_size,
+            &index_link,
+            "",
+            |page, pagination| {
+                // featured posts only go on the first page
+                let featured = if pagination.is_first() { &featured[..] } else { &[][..] };
+                IndexTemplate::new(&self.site, page, featured, pagination)
+            },
+        )
+    }
+
+    fn write_sections(&self, build_dir: &Path, page_size: usize) -> OResult<()> {
+        let sections = self.sections();
+        for section in sections.iter() {
+            let subsections = sections
+                .iter()
+                .filter(|s| parent_section(s) == section.as_str())
+                .map(|s| {
+                    SectionLink::new(format!("/{}/{}.{}", s, INDEX, HTML), section_title(s))
+                }).collect::<Vec<_>>();
+            let blogs = self
+                .visible()
+                .filter(|r| r.resource_type == ResourceType::Blog && r.in_section(section))
+                .map(|r| r.as_blog())
+                .collect::<Vec<_>>();
+            let photos = self
+                .visible()
+                .filter(|r| r.resource_type == ResourceType::Photo && r.in_section(section))
+                .map(|r| r.as_link_label())
+                .collect::<Vec<_>>();
+            let title = section_title(section);
+            let section_dir = format!("/{}", section);
+            let section_link = format!("{}/{}.{}", section_dir, INDEX, HTML);
+            info!("Writing section {}", section);
+            self.write_pages(
+                build_dir,
+                &blogs[..],
+                page_size,
+                &section_link,
+                &section_dir,
+                |page, pagination| {
+                    // subsections and photos only go on the first page
+                    let first = pagination.is_first();
+                    SectionTemplate::new(
+                        &self.site,
+                        section,
+                        &title,
+                        if first { &subsections[..] } else { &[][..] },
+                        page,
+                        if first { &photos[..] } else { &[][..] },
+                        pagination,
+                    )
+                },
+            )?;
+        }
+        Ok(())
+    }
+
+    fn write_tags(&self, build_dir: &Path, page_size: usize) -> OResult<()> {
+        let mut tagged: BTreeMap<String, (&str, Vec<Blog>)> = BTreeMap::new();
+        for resource in self
+            .visible()
+            .filter(|r| r.resource_type == ResourceType::Blog)
+        {
+            for (slug, tag) in resource.tags() {
+                tagged
+                    .entry(slug)
+                    .or_insert_with(|| (tag, vec![]))
+                    .1
+                    .push(resource.as_blog());
+            }
+        }
+        for (slug, &(tag, ref blogs)) in tagged.iter() {
+            let tag_dir = format!("/{}/{}", TAGS_DIR, slug);
+            let tag_link = format!("{}.{}", tag_dir, HTML);
+            info!("Writing tag {}", tag);
+            self.write_pages(
+                build_dir,
+                &blogs[..],
+                page_size,
+                &tag_link,
+                &tag_dir,
+                |page, pagination| {
+                    TagTemplate::new(&self.site, as_tag(tag), blogs.len(), page, pagination)
+                },
+            )?;
+        }
+        let tag_counts = tagged
+            .values()
+            .map(|&(tag, ref blogs)| TagCount::new(as_tag(tag), blogs.len()))
+            .collect::<Vec<_>>();
+        let tags = TagsTemplate::new(&self.site, &tag_counts[..]);
+        let tags_path = build_dir.join(TAGS_DIR).with_extension(HTML);
+        info!("Writing tags file to {:?}", tags_path);
+        self.write_generated(build_dir, &tags_path, tags.render()?)?;
         Ok(())
     }
 
-    fn write_static_templates(&self, build_dir: &Path) -> OResult<()> {
-        let templates: Vec<(&'static str, Box<Template>)> = vec![
-            ("404.html", Box::new(NotFoundTemplate::new())),
-            ("about.html", Box::new(AboutTemplate::new())),
+    fn write_series(&self, build_dir: &Path) -> OResult<()> {
+        for (slug, (name, parts)) in self.series() {
+            let blogs = parts.iter().map(|r| r.as_blog()).collect::<Vec<_>>();
+            let link = series_link(&slug);
+            let template = SeriesTemplate::new(&self.site, name, &link, &blogs[..]);
+            let series_path = build_dir.join(SERIES_DIR).join(&slug).with_extension(HTML);
+            info!("Writing series {} to {:?}", name, series_path);
+            self.write_generated(build_dir, &series_path, template.render()?)?;
+        }
+        Ok(())
+    }
+
+    fn write_archive(&self, build_dir: &Path) -> OResult<()> {
+        let mut years: Vec<ArchiveYear> = vec![];
+        // resources are already sorted newest to oldest, so each month is contiguous
+        for resource in self
+            .visible()
+            .filter(|r| r.resource_type == ResourceType::Blog)
+        {
+            let created = resource.created();
+            if years.last().map_or(true, |y| y.year() != created.year()) {
+                let link = format!("/{}/{}.{}", ARCHIVE_DIR, created.year(), HTML);
+                years.push(ArchiveYear::new(created.year(), link));
+            }
+            let year = years.last_mut().unwrap();
+            let month = created.format("%B").to_string();
+            if year.months().last().map_or(true, |m| m.name() != month) {
+                year.push_month(ArchiveMonth::new(month));
+            }
+            year.push_blog(resource.as_blog());
+        }
+        let archive_link = format!("/{}.{}", ARCHIVE_DIR, HTML);
+        let archive = ArchiveTemplate::new(
+            &self.site,
+            "Archive",
+            &self.site.config.subtitles.archive,
+            &archive_link,
+            &years[..],
+        );
+        let archive_path = build_dir.join(ARCHIVE_DIR).with_extension(HTML);
+        info!("Writing archive file to {:?}", archive_path);
+        self.write_generated(build_dir, &archive_path, archive.render()?)?;
+        for year in years.iter() {
+            let title = year.year().to_string();
+            let year_link = format!("/{}/{}.{}", ARCHIVE_DIR, title, HTML);
+            let template = ArchiveTemplate::new(
+                &self.site,
+                &title,
+                "Archive",
+                &year_link,
+                slice::from_ref(year),
+            );
+            let year_path = build_dir.join(ARCHIVE_DIR).join(&title).with_extension(HTML);
+            info!("Writing archive for {} to {:?}", title, year_path);
+            self.write_generated(build_dir, &year_path, template.render()?)?;
+        }
+        Ok(())
+    }
+
+    fn write_authors(&self, build_dir: &Path, page_size: usize) -> OResult<()> {
+        for author in self.site.authors.iter() {
+            let blogs = self
+                .visible()
+                .filter(|r| r.resource_type == ResourceType::Blog && r.author.id == author.id)
+                .map(|r| r.as_blog())
+                .collect::<Vec<_>>();
+            let link = author_link(&author.id);
+            let dir = link.trim_right_matches(&format!(".{}", HTML));
+            info!("Writing author {}", author.name);
+            self.write_pages(
+                build_dir,
+                &blogs[..],
+                page_size,
+                &link,
+                dir,
+                |page, pagination| AuthorTemplate::new(&self.site, author, page, pagination),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Writes a redirect page at every alias, plus a `_redirects` file for hosts that read one
+    fn write_redirects(&self, build_dir: &Path) -> OResult<()> {
+        let mut redirects = vec![];
+        for resource in self.visible() {
+            let target = match resource.resource_type {
+                ResourceType::Blog => format!("/{}", resource.blog_link()),
+                ResourceType::Page => format!("/{}", resource.page_link()),
+                _ => continue,
+            };
+            for alias in resource.front_matter.aliases.iter() {
+                let alias = format!("/{}", alias.trim_left_matches('/'));
+                let redirect_path = build_dir.join(alias_file(&alias));
+                info!("Writing redirect from {} to {:?}", target, redirect_path);
+                let redirect = RedirectTemplate::new(&self.site, &target);
+                write_html(&self.site, build_dir, &redirect_path, redirect.render()?)?;
+                let base_path = &self.site.config.base_path;
+                redirects.push(format!("{}{} {}{} 301", base_path, alias, base_path, target));
+            }
+        }
+        let redirects_path = build_dir.join(REDIRECTS_FILE);
+        if !redirects.is_empty() {
+            info!("Writing redirects file to {:?}", redirects_path);
+            util::write_file(redirects_path, redirects.join("\n") + "\n")?;
+        } else {
+            util::remove_output(&redirects_path)?;
+        }
+        Ok(())
+    }
+
+    /// Writes generic versions of the pages the site can't do without, unless there's a
+    /// markdown page of the same name
+    fn write_default_pages(&self, build_dir: &Path) -> OResult<()> {
+        let config = &self.site.config;
+        let default_pages = [
+            (
+                "404",
+                "404",
+                "Page Not Found",
+                "There's nothing here.\n\n[Back to the home page](/index.html)",
+            ),
+            ("about", "About", config.subtitle.as_str(), config.description.as_str()),
         ];
-        for (file_name, template) in templates.into_iter() {
-            let template_path = build_dir.join(file_name);
-            info!("Writing static template to {:?}", template_path);
-            util::write_minified_html(template_path, template.render()?)?;
+        for &(name, title, subtitle, markdown) in default_pages.iter() {
+            if self
+                .visible()
+                .any(|r| r.resource_type == ResourceType::Page && r.name == name)
+            {
+                continue;
+            }
+            let html_buf = markdown::render(markdown);
+            let page =
+                PageTemplate::new(&self.site, &html_buf, name, title, subtitle, title, vec![]);
+            let page_path = build_dir.join(name).with_extension(HTML);
+            info!("Writing default page to {:?}", page_path);
+            self.write_generated(build_dir, &page_path, page.render()?)?;
         }
         Ok(())
     }
+
+    fn write_pages<'a, F, T>(
+        &self,
+        build_dir: &Path,
+        blogs: &'a [Blog],
+        page_size: usize,
+        first_link: &str,
+        dir: &str,
+        template: F,
+    ) -> OResult<()>
+    where
+        F: Fn(&'a [Blog], Pagination) -> T,
+        T: Template,
+    {
+        for (page, pagination) in paginate(blogs, page_size, first_link, dir) {
+            let page_path = build_dir.join(pagination.link().trim_left_matches('/'));
+            info!("Writing page {} to {:?}", pagination.link(), page_path);
+            self.write_generated(build_dir, &page_path, template(page, pagination).render()?)?;
+        }
+        Ok(())
+    }
+
+    /// Writes a page that doesn't belong to any one resource, remembering it so it can be
+    /// removed once it isn't needed anymore
+    fn write_generated(&self, build_dir: &Path, path: &Path, html: String) -> OResult<()> {
+        if let Ok(relative) = path.strip_prefix(build_dir) {
+            let output = relative.to_string_lossy().into_owned();
+            self.generated.lock().unwrap().insert(output);
+        }
+        write_html(&self.site, build_dir, path, html)
+    }
+}
+
+/// Splits `blogs` into pages of `page_size`, the first living at `first_link` and the rest at
+/// `<dir>/page/<n>.html`. A page size of zero puts everything on a single page.
+fn paginate<'a>(
+    blogs: &'a [Blog],
+    page_size: usize,
+    first_link: &str,
+    dir: &str,
+) -> Vec<(&'a [Blog], Pagination)> {
+    let mut chunks = if page_size == 0 {
+        vec![blogs]
+    } else {
+        blogs.chunks(page_size).collect::<Vec<_>>()
+    };
+    if chunks.is_empty() {
+        chunks.push(blogs);
+    }
+    let pages = chunks.len();
+    let link = |page: usize| {
+        if page == 1 {
+            first_link.to_owned()
+        } else {
+            format!("{}/{}/{}.{}", dir, PAGE_DIR, page, HTML)
+        }
+    };
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(i, chunk)| {
+            let page = i + 1;
+            let newer = if page > 1 { link(page - 1) } else { String::new() };
+            let older = if page < pages { link(page + 1) } else { String::new() };
+            (chunk, Pagination::new(link(page), page, pages, newer, older))
+        }).collect()
+}
+
+/// Writes rendered html to `path`, with root relative links rewritten for wherever the site is
+/// hosted
+fn write_html(site: &Site, build_dir: &Path, path: &Path, html: String) -> OResult<()> {
+    let depth = path
+        .strip_prefix(build_dir)
+        .map(|relative| relative.components().count().saturating_sub(1))
+        .unwrap_or(0);
+    let options = HtmlOptions {
+        links: site.config.links(depth),
+        fingerprints: &site.fingerprints,
+        minify: site.profile.minify,
+        highlight: site.profile.highlight,
+    };
+    util::write_html(path, html, &options)
+}
+
+/// Whether `dir` is a page bundle, which has an `index.md` and no other posts anywhere under
+/// it. Any other markdown next to an `index.md` is ambiguous, so it's an error.
+fn is_bundle(dir: &Path) -> OResult<bool> {
+    let index = format!("{}.{}", INDEX, MD);
+    if !dir.join(&index).is_file() {
+        return Ok(false);
+    }
+    let mut files = vec![];
+    bundle_files(dir, "", &mut files)?;
+    let is_post = |path: &Path| path.extension().and_then(OsStr::to_str) == Some(MD);
+    if let Some(&(ref path, _)) = files
+        .iter()
+        .find(|&&(ref path, ref file_name)| is_post(path) && *file_name != index)
+    {
+        return Err(OpaqueError::new(format!(
+            "{:?} has an {} so it's a page bundle, which can't also hold {:?}. Bundles are \
+             for a single post and its assets.",
+            dir, index, path
+        )));
+    }
+    Ok(true)
+}
+
+/// Every file under `dir`, paired with its path relative to the bundle, which starts out as
+/// `rel`
+fn bundle_files(dir: &Path, rel: &str, files: &mut Vec<(PathBuf, String)>) -> OResult<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let file_name = file_name(&path)?;
+        if file_name.starts_with('.') {
+            continue;
+        }
+        let rel_path = join_link(&[rel, file_name.as_str()]);
+        if path.is_dir() {
+            bundle_files(&path, &rel_path, files)?;
+        } else {
+            files.push((path, rel_path));
+        }
+    }
+    Ok(())
+}
+
+/// Output directory of a page bundle's assets, which bundled blogs render into as well. Bundled
+/// pages still render to the top level, so their assets get a top level directory.
+fn bundle_dir(resource_type: &ResourceType, section: &str, name: &str) -> String {
+    match *resource_type {
+        ResourceType::Page => name.to_owned(),
+        _ if section.is_empty() => join_link(&[BLOG_DIR, name]),
+        _ => join_link(&[section, name]),
+    }
+}
+
+fn file_name(path: &Path) -> OResult<String> {
+    path.file_name()
+        .and_then(OsStr::to_str)
+        .map(|name| name.to_owned())
+        .ok_or(OpaqueError::new(format!("Invalid filename: {:?}", path)))
+}
+
+/// Joins non-empty path segments with `/`
+fn join_link(parts: &[&str]) -> String {
+    parts
+        .iter()
+        .filter(|part| !part.is_empty())
+        .cloned()
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn parent_section(section: &str) -> &str {
+    section.rfind('/').map(|i| &section[..i]).unwrap_or("")
+}
+
+fn as_tag(tag: &str) -> Tag {
+    Tag::new(
+        tag.to_owned(),
+        format!("/{}/{}.{}", TAGS_DIR, tag_slug(tag), HTML),
+    )
+}
+
+/// File name of a tag's page. Symbols that tell tags apart, like the ones in C++ and C#, are
+/// spelled out rather than dropped.
+fn tag_slug(tag: &str) -> String {
+    // kebab casing leaves non-ascii capitals alone
+    let spelled = tag
+        .to_lowercase()
+        .replace('+', " plus ")
+        .replace('#', " sharp ")
+        .replace('&', " and ")
+        .replace('@', " at ");
+    to_kebab_case(&spelled)
+}
+
+/// Whether an output, relative to the build directory, is one of the listing pages built from
+/// every resource rather than from any one of them
+fn is_generated(output: &str) -> bool {
+    let mut parts = output.split('/').collect::<Vec<_>>();
+    let file = parts.pop().unwrap_or("");
+    match parts.first() {
+        None => {
+            file == REDIRECTS_FILE
+                || [INDEX, "gallery", TAGS_DIR, ARCHIVE_DIR]
+                    .iter()
+                    .any(|name| file == format!("{}.{}", name, HTML))
+        }
+        Some(dir) => {
+            [TAGS_DIR, ARCHIVE_DIR, SERIES_DIR, AUTHORS_DIR].contains(dir)
+                || parts.contains(&PAGE_DIR)
+        }
+    }
+}
+
+/// Where the redirect page for an alias lives, relative to the build directory. Aliases that
+/// aren't html files are treated as directories.
+fn alias_file(alias: &str) -> String {
+    let alias = alias.trim_matches('/');
+    if alias.ends_with(&format!(".{}", HTML)) {
+        alias.to_owned()
+    } else {
+        let index = format!("{}.{}", INDEX, HTML);
+        join_link(&[alias, index.as_str()])
+    }
+}
+
+fn author_link(id: &str) -> String {
+    format!("/{}/{}.{}", AUTHORS_DIR, id, HTML)
+}
+
+fn series_link(slug: &str) -> String {
+    format!("/{}/{}.{}", SERIES_DIR, slug, HTML)
+}
+
+fn section_title(section: &str) -> String {
+    to_title_case(section.rsplit('/').next().unwrap_or(section))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blog(title: &str) -> Blog {
+        Blog {
+            link: format!("/blog/{}.html", title),
+            title: title.to_owned(),
+            created: String::new(),
+            updated: None,
+            description: None,
+            image: None,
+            tags: vec![],
+            summary: String::new(),
+            word_count: 0,
+            reading_time: 1,
+            author_name: String::new(),
+            author_link: String::new(),
+        }
+    }
+
+    #[test]
+    fn pagination() {
+        let blogs = ["a", "b", "c", "d", "e"].iter().map(|t| blog(t)).collect::<Vec<_>>();
+        let pages = paginate(&blogs, 2, "/tags/rust.html", "/tags/rust");
+        let sizes = pages.iter().map(|&(page, _)| page.len()).collect::<Vec<_>>();
+        assert_eq!(sizes, vec![2, 2, 1]);
+        let first = "/tags/rust.html".to_owned();
+        let second = "/tags/rust/page/2.html".to_owned();
+        let third = "/tags/rust/page/3.html".to_owned();
+        assert_eq!(
+            pages[0].1,
+            Pagination::new(first.clone(), 1, 3, String::new(), second.clone())
+        );
+        assert_eq!(
+            pages[1].1,
+            Pagination::new(second.clone(), 2, 3, first, third.clone())
+        );
+        assert_eq!(pages[2].1, Pagination::new(third, 3, 3, second, String::new()));
+    }
+
+    #[test]
+    fn pagination_edges() {
+        let blogs = ["a", "b", "c"].iter().map(|t| blog(t)).collect::<Vec<_>>();
+        // a page size of zero puts everything on one page
+        let pages = paginate(&blogs, 0, "/index.html", "");
+        assert_eq!(pages.len(), 1);
+        assert_eq!(pages[0].0.len(), 3);
+        // listings without posts still get their first page
+        let pages = paginate(&[], 2, "/index.html", "");
+        assert_eq!(pages.len(), 1);
+        let empty = Pagination::new("/index.html".to_owned(), 1, 1, String::new(), String::new());
+        assert_eq!(pages[0].1, empty);
+    }
+
+    #[test]
+    fn alias_files() {
+        assert_eq!(alias_file("/old/post/"), "old/post/index.html");
+        assert_eq!(alias_file("old/post"), "old/post/index.html");
+        assert_eq!(alias_file("/old/post.html"), "old/post.html");
+        assert_eq!(alias_file("/"), "index.html");
+    }
+
+    #[test]
+    fn generated_outputs() {
+        for output in &[
+            "index.html",
+            "gallery.html",
+            "tags.html",
+            "archive.html",
+            "_redirects",
+            "tags/rust.html",
+            "archive/2018.html",
+            "series/intro.html",
+            "authors/ty.html",
+            "page/2.html",
+            "travel/page/2.html",
+        ] {
+            assert!(is_generated(output), "{} is generated", output);
+        }
+        for output in &["about.html", "blog/coffee.html", "travel/index.html", "styles.css"] {
+            assert!(!is_generated(output), "{} isn't generated", output);
+        }
+    }
+
+    #[test]
+    fn tag_slugs() {
+        assert_eq!(tag_slug("Rust"), "rust");
+        assert_eq!(tag_slug("ÜBER"), tag_slug("über"));
+        assert_eq!(tag_slug("C++"), "c-plus-plus");
+        assert_ne!(tag_slug("C#"), tag_slug("C"));
+        assert_eq!(tag_slug("!?"), "");
+    }
 }
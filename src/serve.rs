@@ -1,7 +1,8 @@
 use config::Config;
 use errors::{OResult, OpaqueError};
 use notify::{DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher};
-use resource::SiteResources;
+use resource::{ReadOptions, SiteResources};
+use site::Site;
 use std::path::Path;
 use std::sync::mpsc::channel;
 use std::time::Duration;
@@ -11,14 +12,25 @@ pub fn serve(
     build_dir: &Path,
     static_dir: &Path,
     metadata_file: &Path,
+    site: &Site,
+    options: &ReadOptions,
+    page_size: usize,
 ) -> OResult<()> {
-    let mut config = Config::new(config.timings.clone());
+    let mut config = config.clone();
     let (tx, rx) = channel();
     let mut watcher: RecommendedWatcher = Watcher::new(tx, Duration::from_secs(1))?;
-    watcher.watch(static_dir, RecursiveMode::NonRecursive)?;
-    let resources = SiteResources::read_resources(&static_dir, &config)?;
-    resources.build_all(build_dir, false)?;
-    config = Config::new(resources.timings());
+    watcher.watch(static_dir, RecursiveMode::Recursive)?;
+    // the site files only live outside the static directory when given on the command line
+    let files = &site.files;
+    for file in &[&files.site_file, &files.authors_file, &files.data_dir] {
+        if file.exists() && !file.starts_with(static_dir) {
+            watcher.watch(file, RecursiveMode::Recursive)?;
+        }
+    }
+    let mut site = site.clone();
+    let resources = SiteResources::read_resources(&static_dir, &config, &site, options)?;
+    resources.build_all(build_dir, false, page_size)?;
+    config = resources.metadata(build_dir, &config)?;
     loop {
         match rx.recv()? {
             DebouncedEvent::Error(e, _) => {
@@ -28,14 +40,36 @@ pub fn serve(
             DebouncedEvent::Rescan | DebouncedEvent::Chmod(_) => {}
             _ => {
                 info!("Detected changes, rebuilding files");
-                let resources = SiteResources::read_resources(&static_dir, &config)?;
-                if let Err(e) = resources.build_all(build_dir, false) {
+                match site.reload() {
+                    Ok(reloaded) => site = reloaded,
+                    Err(e) => {
+                        eprintln!("Could not read site files due to {}", e);
+                        continue;
+                    }
+                }
+                let resources =
+                    match SiteResources::read_resources(&static_dir, &config, &site, options) {
+                        Ok(resources) => resources,
+                        Err(e) => {
+                            eprintln!("Could not read resources due to {}", e);
+                            continue;
+                        }
+                    };
+                if let Err(e) = resources.build_all(build_dir, false, page_size) {
                     eprintln!("Could not build due to {}", e);
                     continue;
                 }
-                let updated_config = Config::new(resources.timings());
+                let updated_config = match resources.metadata(build_dir, &config) {
+                    Ok(updated_config) => updated_config,
+                    Err(e) => {
+                        eprintln!("Could not update metadata due to {}", e);
+                        continue;
+                    }
+                };
                 if config != updated_config {
-                    updated_config.to_file(&metadata_file)?;
+                    if let Err(e) = updated_config.to_file(&metadata_file) {
+                        eprintln!("Could not write metadata file due to {}", e);
+                    }
                     config = updated_config;
                 }
             }
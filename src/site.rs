@@ -0,0 +1,268 @@
+use authors::Authors;
+use data::SiteData;
+use errors::{OResult, OpaqueError};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use toml;
+use util::{content_hash, read_file, Highlight, Links};
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct NavLink {
+    pub label: String,
+    pub url: String,
+}
+
+impl NavLink {
+    fn new(label: &str, url: &str) -> Self {
+        NavLink {
+            label: label.to_owned(),
+            url: url.to_owned(),
+        }
+    }
+}
+
+/// Subtitles of the generated listing pages
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Subtitles {
+    pub tags: String,
+    pub archive: String,
+    pub gallery: String,
+}
+
+impl Default for Subtitles {
+    fn default() -> Self {
+        Subtitles {
+            tags: "Posts by topic".to_owned(),
+            archive: "Every post, ever".to_owned(),
+            gallery: "Occasional Photographer".to_owned(),
+        }
+    }
+}
+
+/// How a build treats its output, picked with `--profile`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Profile {
+    pub minify: bool,
+    pub highlight: Highlight,
+    /// Builds drafts and scheduled posts
+    pub drafts: bool,
+    /// Puts a hash of their contents in the names of styles and scripts, so they can be cached
+    /// forever
+    pub fingerprint: bool,
+    /// Writes a gzipped copy of every html, css and js file next to it
+    pub compress: bool,
+}
+
+impl Profile {
+    fn dev() -> Self {
+        Profile {
+            minify: false,
+            highlight: Highlight::Classes,
+            drafts: true,
+            fingerprint: false,
+            compress: false,
+        }
+    }
+
+    fn prod() -> Self {
+        Profile {
+            minify: true,
+            highlight: Highlight::Inline,
+            drafts: false,
+            fingerprint: true,
+            compress: true,
+        }
+    }
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Profile::prod()
+    }
+}
+
+/// A `[profiles.<name>]` section of the site file, overriding the built in profile of that name
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+pub struct ProfileConfig {
+    minify: Option<bool>,
+    highlight: Option<Highlight>,
+    drafts: Option<bool>,
+    fingerprint: Option<bool>,
+    compress: Option<bool>,
+}
+
+/// Everything that makes the site someone's in particular, read from `site.toml`. Anything
+/// left out of the file falls back to a generic value.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct SiteConfig {
+    /// Scheme and host that absolute urls are built on, without a trailing slash
+    pub domain: String,
+    /// Path the site is hosted under, like `/~team`, empty when it's at the root of the domain
+    pub base_path: String,
+    /// Writes every link relative to its page so the build can be browsed from `file://`
+    pub relative_links: bool,
+    /// Shown in browser titles and as the og:site_name
+    pub name: String,
+    /// Heading and subtitle of the index
+    pub title: String,
+    pub subtitle: String,
+    pub description: String,
+    /// Paragraph welcoming visitors at the top of the index
+    pub welcome: String,
+    /// Author of any page that isn't a post
+    pub author: String,
+    /// Path of the og:image for pages that don't have their own
+    pub image: String,
+    pub nav: Vec<NavLink>,
+    pub copyright: String,
+    pub copyright_link: String,
+    pub subtitles: Subtitles,
+    pub profiles: BTreeMap<String, ProfileConfig>,
+}
+
+impl Default for SiteConfig {
+    fn default() -> Self {
+        SiteConfig {
+            domain: "http://localhost:8080".to_owned(),
+            base_path: String::new(),
+            relative_links: false,
+            name: "Blog".to_owned(),
+            title: "Blog".to_owned(),
+            subtitle: String::new(),
+            description: String::new(),
+            welcome: String::new(),
+            author: "Anonymous".to_owned(),
+            image: String::new(),
+            nav: vec![
+                NavLink::new("Home", "/index.html"),
+                NavLink::new("About", "/about.html"),
+                NavLink::new("Gallery", "/gallery.html"),
+                NavLink::new("Tags", "/tags.html"),
+                NavLink::new("Archive", "/archive.html"),
+            ],
+            copyright: String::new(),
+            copyright_link: String::new(),
+            subtitles: Subtitles::default(),
+            profiles: BTreeMap::new(),
+        }
+    }
+}
+
+impl SiteConfig {
+    pub fn from_file<P: AsRef<Path>>(site_file: P) -> OResult<Self> {
+        let mut config: SiteConfig = toml::from_slice(read_file(site_file)?.as_bytes())?;
+        config.domain = config.domain.trim_right_matches('/').to_owned();
+        let base_path = config.base_path.trim_matches('/').to_owned();
+        config.base_path = if base_path.is_empty() {
+            String::new()
+        } else {
+            format!("/{}", base_path)
+        };
+        Ok(config)
+    }
+
+    pub fn absolute_url(&self, path: &str) -> String {
+        format!("{}{}{}", self.domain, self.base_path, path)
+    }
+
+    /// How links get written into a page `depth` directories below the build directory
+    pub fn links(&self, depth: usize) -> Links {
+        if self.relative_links {
+            Links::Relative(depth)
+        } else {
+            Links::Prefixed(self.base_path.clone())
+        }
+    }
+
+    /// One of the built in `dev` or `prod` profiles, with any overrides from the site file
+    pub fn profile(&self, name: &str) -> OResult<Profile> {
+        let mut profile = match name {
+            "dev" => Profile::dev(),
+            "prod" => Profile::prod(),
+            _ => return Err(OpaqueError::new(format!("Unknown build profile {}", name))),
+        };
+        if let Some(config) = self.profiles.get(name) {
+            profile.minify = config.minify.unwrap_or(profile.minify);
+            profile.highlight = config.highlight.unwrap_or(profile.highlight);
+            profile.drafts = config.drafts.unwrap_or(profile.drafts);
+            profile.fingerprint = config.fingerprint.unwrap_or(profile.fingerprint);
+            profile.compress = config.compress.unwrap_or(profile.compress);
+        }
+        Ok(profile)
+    }
+
+    /// Browser title of a page on the site
+    pub fn browser_title(&self, title: &str) -> String {
+        format!("{} | {}", title, self.name)
+    }
+}
+
+/// Where the site, authors and data files live, and the profile the site is built with
+#[derive(Debug, Clone)]
+pub struct SiteFiles {
+    pub site_file: PathBuf,
+    pub authors_file: PathBuf,
+    pub data_dir: PathBuf,
+    pub profile: String,
+}
+
+/// Everything about the site that isn't a resource, shared by every page that gets rendered
+#[derive(Debug, Clone)]
+pub struct Site {
+    pub config: SiteConfig,
+    pub profile: Profile,
+    pub authors: Authors,
+    /// Shared rather than copied into every page that gets rendered
+    pub data: Arc<SiteData>,
+    /// Fingerprinted links of styles and scripts keyed by their plain link, filled in once the
+    /// resources are read
+    pub fingerprints: BTreeMap<String, String>,
+    pub files: SiteFiles,
+}
+
+impl Site {
+    /// Reads the site, authors and data files, using defaults for the ones that don't exist
+    pub fn from_files(files: SiteFiles) -> OResult<Self> {
+        let config = if files.site_file.exists() {
+            SiteConfig::from_file(&files.site_file)?
+        } else {
+            info!("No site file at {:?}, using the default site config", files.site_file);
+            SiteConfig::default()
+        };
+        let authors = if files.authors_file.exists() {
+            Authors::from_file(&files.authors_file)?
+        } else {
+            info!("No authors file at {:?}, using the site's author", files.authors_file);
+            Authors::single(&config.author, &config.subtitle)
+        };
+        let profile = config.profile(&files.profile)?;
+        info!("Using the {} profile: {:?}", files.profile, profile);
+        let data = SiteData::from_dir(&files.data_dir)?;
+        Ok(Site {
+            config,
+            profile,
+            authors,
+            data: Arc::new(data),
+            fingerprints: BTreeMap::new(),
+            files,
+        })
+    }
+
+    /// Reads the site files again, for picking up changes while serving
+    pub fn reload(&self) -> OResult<Self> {
+        Site::from_files(self.files.clone())
+    }
+
+    /// Hash of everything read from the site, authors and data files along with the build
+    /// profile, which changes whenever every page needs rendering again
+    pub fn hash(&self) -> String {
+        let inputs = format!(
+            "{:?}{:?}{:?}{:?}",
+            self.config, self.profile, self.authors, self.data
+        );
+        content_hash(inputs.as_bytes())
+    }
+}
@@ -1,4 +1,9 @@
 use askama::Template;
+use authors::Author;
+use data::{DataSet, SiteData};
+use markdown::TocEntry;
+use site::{Site, SiteConfig};
+use std::sync::Arc;
 
 #[derive(Debug, PartialEq)]
 pub enum LinkType {
@@ -40,21 +45,25 @@ impl Meta {
         Meta::new("og:title", title)
     }
 
-    fn og_image(image: &str) -> Self {
-        Meta::new("og:image", image)
+    /// Root relative images are made absolute, since og tags are read off the site
+    fn og_image(site: &SiteConfig, image: &str) -> Self {
+        if image.starts_with('/') {
+            Meta::new("og:image", site.absolute_url(image))
+        } else {
+            Meta::new("og:image", image)
+        }
     }
 
     fn og_type(r#type: &str) -> Self {
         Meta::new("og:type", r#type)
     }
 
-    fn og_url(url: &str) -> Self {
-        let domain = "https://ty-needs.coffee";
-        Meta::new("og:url", format!("{}{}", domain, url))
+    fn og_url(site: &SiteConfig, url: &str) -> Self {
+        Meta::new("og:url", site.absolute_url(url))
     }
 
-    fn og_site_name() -> Self {
-        Meta::new("og:site_name", "Ty Needs Coffee")
+    fn og_site_name(name: &str) -> Self {
+        Meta::new("og:site_name", name)
     }
 }
 
@@ -67,10 +76,14 @@ pub struct BaseTemplate {
     links: Vec<Link>,
     metas: Vec<Meta>,
     description: String,
+    author: String,
+    site: SiteConfig,
+    data: Arc<SiteData>,
 }
 
 impl BaseTemplate {
     fn new<S1: ToString, S2: ToString>(
+        site: &Site,
         title: S1,
         subtitle: S1,
         browser_title: S1,
@@ -82,10 +95,11 @@ impl BaseTemplate {
             Link::new("/styles.css", LinkType::Style),
             Link::new("/favicon.ico", LinkType::Icon),
         ];
-        let mut common_meta = vec![
-            Meta::og_site_name(),
-            Meta::og_image("https://ty-needs.coffee/image/coffee.jpg"),
-        ];
+        let mut common_meta = vec![Meta::og_site_name(&site.config.name)];
+        let has_image = metas.iter().any(|meta| meta.name == "og:image");
+        if !has_image && !site.config.image.is_empty() {
+            common_meta.push(Meta::og_image(&site.config, &site.config.image));
+        }
         links.append(&mut base_links);
         metas.append(&mut common_meta);
         BaseTemplate {
@@ -95,25 +109,70 @@ impl BaseTemplate {
             description: description.to_string(),
             links,
             metas,
+            author: site.config.author.clone(),
+            site: site.config.clone(),
+            data: site.data.clone(),
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+pub struct Tag {
+    name: String,
+    link: String,
+}
+
+impl Tag {
+    pub fn new(name: String, link: String) -> Self {
+        Tag { name, link }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Blog {
+    pub link: String,
+    pub title: String,
+    pub created: String,
+    pub updated: Option<String>,
+    pub description: Option<String>,
+    pub image: Option<String>,
+    pub tags: Vec<Tag>,
+    /// Rendered html excerpt shown on listing cards
+    pub summary: String,
+    pub word_count: usize,
+    /// Estimated minutes to read
+    pub reading_time: usize,
+    pub author_name: String,
+    pub author_link: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Pagination {
     link: String,
-    title: String,
-    created: String,
+    page: usize,
+    pages: usize,
+    newer: String,
+    older: String,
 }
 
-impl Blog {
-    pub fn new(link: String, title: String, created: String) -> Self {
-        Blog {
+impl Pagination {
+    pub fn new(link: String, page: usize, pages: usize, newer: String, older: String) -> Self {
+        Pagination {
             link,
-            title,
-            created,
+            page,
+            pages,
+            newer,
+            older,
         }
     }
+
+    pub fn link(&self) -> &str {
+        &self.link
+    }
+
+    pub fn is_first(&self) -> bool {
+        self.page == 1
+    }
 }
 
 #[derive(Debug, Template)]
@@ -121,63 +180,431 @@ impl Blog {
 pub struct IndexTemplate<'a> {
     _parent: BaseTemplate,
     blogs: &'a [Blog],
+    featured: &'a [Blog],
+    pagination: Pagination,
 }
 
 impl<'a> IndexTemplate<'a> {
-    pub fn new(blogs: &'a [Blog]) -> Self {
-        let description = "Ty Coghlan's personal website and coffee-fueled blog.";
+    pub fn new(
+        site: &Site,
+        blogs: &'a [Blog],
+        featured: &'a [Blog],
+        pagination: Pagination,
+    ) -> Self {
+        let config = &site.config;
         let date_script = Link::new("/date_script.js", LinkType::Script);
         let base = BaseTemplate::new(
-            "TY COGHLAN",
-            "Software Developer, Coffee Drinker",
-            "Ty Needs Coffee",
-            description,
+            site,
+            &config.title,
+            &config.subtitle,
+            &config.name,
+            &config.description,
             vec![date_script],
             vec![
                 Meta::og_type("website"),
-                Meta::og_url("/"),
-                Meta::og_title("Ty Needs Coffee"),
+                Meta::og_url(config, if pagination.is_first() { "/" } else { pagination.link() }),
+                Meta::og_title(&config.name),
             ],
         );
         IndexTemplate {
             _parent: base,
             blogs: blogs,
+            featured,
+            pagination,
         }
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct PostLink {
+    title: String,
+    link: String,
+}
+
+impl PostLink {
+    pub fn new(title: String, link: String) -> Self {
+        PostLink { title, link }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SeriesPart {
+    post: PostLink,
+    current: bool,
+}
+
+impl SeriesPart {
+    pub fn new(post: PostLink, current: bool) -> Self {
+        SeriesPart { post, current }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Series {
+    name: String,
+    link: String,
+    part: usize,
+    total: usize,
+    parts: Vec<SeriesPart>,
+}
+
+impl Series {
+    pub fn new(
+        name: String,
+        link: String,
+        part: usize,
+        total: usize,
+        parts: Vec<SeriesPart>,
+    ) -> Self {
+        Series {
+            name,
+            link,
+            part,
+            total,
+            parts,
+        }
+    }
+}
+
+/// Information from other posts that a blog needs when it is rendered
+#[derive(Debug, Default, Clone)]
+pub struct BlogContext {
+    pub series: Option<Series>,
+    pub previous: Option<PostLink>,
+    pub next: Option<PostLink>,
+    pub related: Vec<Blog>,
+}
+
 #[derive(Debug, Template)]
-#[template(path = "blog.html", escape = "none")]
+#[template(path = "blog.html")]
 pub struct BlogTemplate<'a> {
     _parent: BaseTemplate,
     blog_html: &'a str,
+    tags: Vec<Tag>,
+    author_name: String,
+    author_link: String,
+    context: BlogContext,
+    toc: Vec<TocEntry>,
 }
 
 impl<'a> BlogTemplate<'a> {
-    pub fn new(blog_html: &'a str, mut blog: Blog) -> Self {
-        let description = "Will make this an actual description eventually";
-        blog.link.insert_str(0, "/");
+    pub fn new(
+        site: &Site,
+        blog_html: &'a str,
+        blog: Blog,
+        context: BlogContext,
+        toc: Vec<TocEntry>,
+    ) -> Self {
+        let description = blog.description.clone().unwrap_or_else(|| blog.title.clone());
         let mut blog_browser_title = blog.title.clone();
-        let suffix = " | Ty Needs Coffee";
+        let suffix = format!(" | {}", site.config.name);
         if blog_browser_title.len() <= 70 - suffix.len() {
-            blog_browser_title.push_str(suffix);
+            blog_browser_title.push_str(&suffix);
         }
-        let base = BaseTemplate::new(
+        let mut metas = vec![
+            Meta::og_type("article"),
+            Meta::og_url(&site.config, &blog.link),
+            Meta::og_title(&blog.title),
+            Meta::new("article:published_time", &blog.created),
+        ];
+        if let Some(ref updated) = blog.updated {
+            metas.push(Meta::new("article:modified_time", updated));
+        }
+        if let Some(ref image) = blog.image {
+            metas.push(Meta::og_image(&site.config, image));
+        }
+        for tag in blog.tags.iter() {
+            metas.push(Meta::new("article:tag", &tag.name));
+        }
+        metas.push(Meta::new(
+            "article:author",
+            site.config.absolute_url(&blog.author_link),
+        ));
+        let mut base = BaseTemplate::new(
+            site,
             blog.title.to_uppercase(),
-            "By Ty Coghlan".to_owned(),
+            format!(
+                "By {} · {} words · {} min read",
+                blog.author_name, blog.word_count, blog.reading_time
+            ),
             blog_browser_title,
             description,
             vec![],
-            vec![
-                Meta::og_type("article"),
-                Meta::og_url(&blog.link),
-                Meta::og_title(&blog.title),
-                //
-            ],
+            metas,
         );
+        base.author = blog.author_name.clone();
         BlogTemplate {
             _parent: base,
             blog_html,
+            tags: blog.tags,
+            author_name: blog.author_name,
+            author_link: blog.author_link,
+            context,
+            toc,
+        }
+    }
+}
+
+#[derive(Debug, Template)]
+#[template(path = "tag.html")]
+pub struct TagTemplate<'a> {
+    _parent: BaseTemplate,
+    blogs: &'a [Blog],
+    pagination: Pagination,
+}
+
+impl<'a> TagTemplate<'a> {
+    pub fn new(
+        site: &Site,
+        tag: Tag,
+        count: usize,
+        blogs: &'a [Blog],
+        pagination: Pagination,
+    ) -> Self {
+        let description = format!("Posts tagged {}", tag.name);
+        let date_script = Link::new("/date_script.js", LinkType::Script);
+        let base = BaseTemplate::new(
+            site,
+            tag.name.to_uppercase(),
+            format!("{} posts", count),
+            site.config.browser_title(&tag.name),
+            description,
+            vec![date_script],
+            vec![
+                Meta::og_type("website"),
+                Meta::og_url(&site.config, pagination.link()),
+                Meta::og_title(&tag.name),
+            ],
+        );
+        TagTemplate {
+            _parent: base,
+            blogs,
+            pagination,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct TagCount {
+    tag: Tag,
+    count: usize,
+}
+
+impl TagCount {
+    pub fn new(tag: Tag, count: usize) -> Self {
+        TagCount { tag, count }
+    }
+}
+
+#[derive(Debug, Template)]
+#[template(path = "tags.html")]
+pub struct TagsTemplate<'a> {
+    _parent: BaseTemplate,
+    tag_counts: &'a [TagCount],
+}
+
+impl<'a> TagsTemplate<'a> {
+    pub fn new(site: &Site, tag_counts: &'a [TagCount]) -> Self {
+        let base = BaseTemplate::new(
+            site,
+            "TAGS",
+            site.config.subtitles.tags.as_str(),
+            site.config.browser_title("Tags").as_str(),
+            &site.config.subtitles.tags,
+            vec![],
+            vec![
+                Meta::og_type("website"),
+                Meta::og_url(&site.config, "/tags.html"),
+                Meta::og_title("Tags"),
+            ],
+        );
+        TagsTemplate {
+            _parent: base,
+            tag_counts,
+        }
+    }
+}
+
+#[derive(Debug, Template)]
+#[template(path = "series.html")]
+pub struct SeriesTemplate<'a> {
+    _parent: BaseTemplate,
+    blogs: &'a [Blog],
+}
+
+impl<'a> SeriesTemplate<'a> {
+    pub fn new(site: &Site, name: &str, link: &str, blogs: &'a [Blog]) -> Self {
+        let description = format!("{}, a series in {} parts", name, blogs.len());
+        let date_script = Link::new("/date_script.js", LinkType::Script);
+        let base = BaseTemplate::new(
+            site,
+            name.to_uppercase(),
+            format!("A series in {} parts", blogs.len()),
+            site.config.browser_title(name),
+            description,
+            vec![date_script],
+            vec![
+                Meta::og_type("website"),
+                Meta::og_url(&site.config, link),
+                Meta::og_title(name),
+            ],
+        );
+        SeriesTemplate {
+            _parent: base,
+            blogs,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ArchiveMonth {
+    name: String,
+    blogs: Vec<Blog>,
+}
+
+impl ArchiveMonth {
+    pub fn new(name: String) -> Self {
+        ArchiveMonth {
+            name,
+            blogs: vec![],
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+#[derive(Debug)]
+pub struct ArchiveYear {
+    year: i32,
+    link: String,
+    months: Vec<ArchiveMonth>,
+}
+
+impl ArchiveYear {
+    pub fn new(year: i32, link: String) -> Self {
+        ArchiveYear {
+            year,
+            link,
+            months: vec![],
+        }
+    }
+
+    pub fn year(&self) -> i32 {
+        self.year
+    }
+
+    pub fn months(&self) -> &[ArchiveMonth] {
+        &self.months
+    }
+
+    pub fn push_month(&mut self, month: ArchiveMonth) {
+        self.months.push(month);
+    }
+
+    /// Adds the blog to the most recently pushed month
+    pub fn push_blog(&mut self, blog: Blog) {
+        if let Some(month) = self.months.last_mut() {
+            month.blogs.push(blog);
+        }
+    }
+}
+
+#[derive(Debug, Template)]
+#[template(path = "archive.html")]
+pub struct ArchiveTemplate<'a> {
+    _parent: BaseTemplate,
+    years: &'a [ArchiveYear],
+}
+
+impl<'a> ArchiveTemplate<'a> {
+    pub fn new(
+        site: &Site,
+        title: &str,
+        subtitle: &str,
+        link: &str,
+        years: &'a [ArchiveYear],
+    ) -> Self {
+        let description = format!("{} of every post on {}", title, site.config.name);
+        let base = BaseTemplate::new(
+            site,
+            title.to_uppercase(),
+            subtitle.to_owned(),
+            site.config.browser_title(title),
+            description,
+            vec![],
+            vec![
+                Meta::og_type("website"),
+                Meta::og_url(&site.config, link),
+                Meta::og_title(title),
+            ],
+        );
+        ArchiveTemplate {
+            _parent: base,
+            years,
+        }
+    }
+}
+
+#[derive(Debug, Template)]
+#[template(path = "author.html")]
+pub struct AuthorTemplate<'a> {
+    _parent: BaseTemplate,
+    author: &'a Author,
+    blogs: &'a [Blog],
+    pagination: Pagination,
+}
+
+impl<'a> AuthorTemplate<'a> {
+    pub fn new(
+        site: &Site,
+        author: &'a Author,
+        blogs: &'a [Blog],
+        pagination: Pagination,
+    ) -> Self {
+        let description = format!("Posts by {}", author.name);
+        let date_script = Link::new("/date_script.js", LinkType::Script);
+        let mut metas = vec![
+            Meta::og_type("profile"),
+            Meta::og_url(&site.config, pagination.link()),
+            Meta::og_title(&author.name),
+        ];
+        if let Some(ref avatar) = author.avatar {
+            metas.push(Meta::og_image(&site.config, avatar));
+        }
+        let mut base = BaseTemplate::new(
+            site,
+            author.name.to_uppercase(),
+            author.bio.clone(),
+            site.config.browser_title(&author.name),
+            description,
+            vec![date_script],
+            metas,
+        );
+        base.author = author.name.clone();
+        AuthorTemplate {
+            _parent: base,
+            author,
+            blogs,
+            pagination,
+        }
+    }
+}
+
+/// A bare page sending visitors from an old path to where the content lives now
+#[derive(Debug, Template)]
+#[template(path = "redirect.html")]
+pub struct RedirectTemplate {
+    link: String,
+    canonical: String,
+}
+
+impl RedirectTemplate {
+    pub fn new(site: &Site, link: &str) -> Self {
+        RedirectTemplate {
+            link: link.to_owned(),
+            canonical: site.config.absolute_url(link),
         }
     }
 }
@@ -207,13 +634,13 @@ pub struct GalleryTemplate<'a> {
 }
 
 impl<'a> GalleryTemplate<'a> {
-    pub fn new(label_links: &'a [LinkLabel]) -> Self {
-        let description = "Just my amateur photos";
+    pub fn new(site: &Site, label_links: &'a [LinkLabel]) -> Self {
         let base = BaseTemplate::new(
-            "TY COGHLAN",
-            "Occasional Photographer",
-            "Gallery | Ty Needs Coffee",
-            description,
+            site,
+            &site.config.title,
+            &site.config.subtitles.gallery,
+            &site.config.browser_title("Gallery"),
+            &site.config.subtitles.gallery,
             vec![],
             vec![],
         );
@@ -224,44 +651,100 @@ impl<'a> GalleryTemplate<'a> {
     }
 }
 
+#[derive(Debug)]
+pub struct SectionLink {
+    link: String,
+    title: String,
+}
+
+impl SectionLink {
+    pub fn new(link: String, title: String) -> Self {
+        SectionLink { link, title }
+    }
+}
+
 #[derive(Debug, Template)]
-#[template(path = "about.html")]
-pub struct AboutTemplate {
+#[template(path = "section.html")]
+pub struct SectionTemplate<'a> {
     _parent: BaseTemplate,
+    subsections: &'a [SectionLink],
+    blogs: &'a [Blog],
+    label_links: &'a [LinkLabel],
+    pagination: Pagination,
 }
 
-impl AboutTemplate {
-    pub fn new() -> Self {
-        let description = "Ty's bio, relevant links, and coffee preferences.";
+impl<'a> SectionTemplate<'a> {
+    pub fn new(
+        site: &Site,
+        section: &str,
+        title: &str,
+        subsections: &'a [SectionLink],
+        blogs: &'a [Blog],
+        label_links: &'a [LinkLabel],
+        pagination: Pagination,
+    ) -> Self {
+        let description = format!("Everything filed under {}", title);
+        let date_script = Link::new("/date_script.js", LinkType::Script);
         let base = BaseTemplate::new(
-            "TY COGHLAN",
-            "(No, it's not short for Tyler)",
-            "About | Ty Needs Coffee",
+            site,
+            title.to_uppercase(),
+            section.to_owned(),
+            site.config.browser_title(title),
             description,
-            vec![],
-            vec![],
+            vec![date_script],
+            vec![
+                Meta::og_type("website"),
+                Meta::og_url(&site.config, pagination.link()),
+                Meta::og_title(title),
+            ],
         );
-        AboutTemplate { _parent: base }
+        SectionTemplate {
+            _parent: base,
+            subsections,
+            blogs,
+            label_links,
+            pagination,
+        }
     }
 }
 
 #[derive(Debug, Template)]
-#[template(path = "404.html")]
-pub struct NotFoundTemplate {
+#[template(path = "page.html")]
+pub struct PageTemplate<'a> {
     _parent: BaseTemplate,
+    page_html: &'a str,
+    id: String,
+    data_sets: Vec<&'a DataSet>,
 }
 
-impl NotFoundTemplate {
-    pub fn new() -> Self {
-        let description = "404 Page Not Found";
+impl<'a> PageTemplate<'a> {
+    pub fn new(
+        site: &Site,
+        page_html: &'a str,
+        name: &str,
+        title: &str,
+        subtitle: &str,
+        description: &str,
+        data_sets: Vec<&'a DataSet>,
+    ) -> Self {
         let base = BaseTemplate::new(
-            "404",
-            "Page Not Found",
-            "404 | Ty Needs Coffee",
+            site,
+            title.to_uppercase(),
+            subtitle.to_owned(),
+            site.config.browser_title(title),
             description,
             vec![],
-            vec![],
+            vec![
+                Meta::og_type("website"),
+                Meta::og_url(&site.config, &format!("/{}.html", name)),
+                Meta::og_title(title),
+            ],
         );
-        NotFoundTemplate { _parent: base }
+        PageTemplate {
+            _parent: base,
+            page_html,
+            id: name.to_owned(),
+            data_sets,
+        }
     }
 }
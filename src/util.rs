@@ -1,19 +1,24 @@
 use errors::OResult;
 use html5ever::interface::QualName;
-use html5ever::rcdom::{Handle, Node, NodeData, RcDom};
+use html5ever::rcdom::{Handle, Node, NodeData, RcDom, SerializableHandle};
+use html5ever::serialize::{serialize, SerializeOpts};
 use html5ever::tree_builder::Attribute;
 use html5ever::LocalName;
 use html5ever_ext::RcDomExt;
 use html5ever_ext::UltraMinifyingHtmlSerializer;
 use regex::Regex;
+use flate2::write::GzEncoder;
+use flate2::Compression;
 use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
 use std::fs::{self, File};
 use std::io::{BufReader, BufWriter, Read, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use syntect::easy::HighlightLines;
 use syntect::highlighting::{Color, FontStyle, Style, ThemeSet};
-use syntect::parsing::SyntaxSet;
+use syntect::parsing::{ParseState, ScopeStack, SyntaxDefinition, SyntaxSet};
 
 pub fn read_file<P: AsRef<Path>>(path: P) -> OResult<String> {
     let bytes = read_bytes(path)?;
@@ -21,6 +26,16 @@ pub fn read_file<P: AsRef<Path>>(path: P) -> OResult<String> {
     Ok(string)
 }
 
+/// 64 bit FNV-1a as hex, which unlike the std hashers is stable across builds and platforms
+pub fn content_hash(bytes: &[u8]) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in bytes {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    format!("{:016x}", hash)
+}
+
 pub fn read_bytes<P: AsRef<Path>>(path: P) -> OResult<Vec<u8>> {
     let path = path.as_ref();
     let mut br = BufReader::new(File::open(path)?);
@@ -29,49 +44,202 @@ pub fn read_bytes<P: AsRef<Path>>(path: P) -> OResult<Vec<u8>> {
     Ok(result)
 }
 
-pub fn write_minified_html<P, B>(path: P, content: B) -> OResult<()>
+/// How root relative links in generated html are written out
+#[derive(Debug, Clone, PartialEq)]
+pub enum Links {
+    /// Prefixed with the path the site is hosted under, left alone when that's empty
+    Prefixed(String),
+    /// Made relative to the page, so the output works from `file://`. Holds how many
+    /// directories below the build directory the page is.
+    Relative(usize),
+}
+
+impl Links {
+    fn rewrite(&self, url: &str) -> Option<String> {
+        if !url.starts_with('/') || url.starts_with("//") {
+            return None;
+        }
+        match *self {
+            Links::Prefixed(ref prefix) if prefix.is_empty() => None,
+            Links::Prefixed(ref prefix) => Some(format!("{}{}", prefix, url)),
+            Links::Relative(depth) => {
+                let mut relative = "../".repeat(depth);
+                relative.push_str(&url[1..]);
+                // there's no server to fill in directory indexes from a file
+                if relative.is_empty() || relative.ends_with('/') {
+                    relative.push_str("index.html");
+                }
+                Some(relative)
+            }
+        }
+    }
+}
+
+/// How code blocks get syntax highlighted
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Highlight {
+    /// Left as plain text
+    Off,
+    /// Colored with inline styles, so they look right without any stylesheet
+    Inline,
+    /// Marked with classes named after their syntax scopes, like `keyword control rust`, which
+    /// read well in the html source and are colored by the site's stylesheet
+    Classes,
+}
+
+/// What happens to generated html on its way to disk
+#[derive(Debug)]
+pub struct HtmlOptions<'a> {
+    pub links: Links,
+    /// Fingerprinted links to use in place of the plain ones they're keyed by
+    pub fingerprints: &'a BTreeMap<String, String>,
+    pub minify: bool,
+    pub highlight: Highlight,
+}
+
+impl<'a> HtmlOptions<'a> {
+    fn rewrite(&self, url: &str) -> Option<String> {
+        let end = url.find(|c: char| c == '?' || c == '#').unwrap_or(url.len());
+        let (path, rest) = url.split_at(end);
+        match self.fingerprints.get(path) {
+            Some(fingerprinted) => {
+                let url = format!("{}{}", fingerprinted, rest);
+                Some(self.links.rewrite(&url).unwrap_or(url))
+            }
+            None => self.links.rewrite(url),
+        }
+    }
+}
+
+pub fn write_html<P, B>(path: P, content: B, options: &HtmlOptions) -> OResult<()>
 where
     P: AsRef<Path>,
     B: AsRef<[u8]>,
 {
+    create_parent_dirs(&path)?;
     let f = File::create(path)?;
-    let bw = BufWriter::new(f);
+    let mut bw = BufWriter::new(f);
     let mut dom = RcDom::from_bytes(content.as_ref());
-    inspect_dom(&mut dom);
-    let mut mini = UltraMinifyingHtmlSerializer::new(false, false, false, bw);
-    mini.serialize_rc_dom(&dom, true)?;
+    inspect_dom(&mut dom, options);
+    if options.minify {
+        let mut mini = UltraMinifyingHtmlSerializer::new(false, false, false, bw);
+        mini.serialize_rc_dom(&dom, true)?;
+    } else {
+        let document: SerializableHandle = dom.document.clone().into();
+        serialize(&mut bw, &document, SerializeOpts::default())?;
+    }
+    Ok(())
+}
+
+/// Writes a gzipped copy next to every file in `dir` with one of the `extensions`, for servers
+/// that can send precompressed files. Copies newer than their file are left alone.
+pub fn gzip_files(dir: &Path, extensions: &[&str]) -> OResult<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            gzip_files(&path, extensions)?;
+            continue;
+        }
+        let compressible = path
+            .extension()
+            .and_then(OsStr::to_str)
+            .map_or(false, |ext| extensions.contains(&ext));
+        if !compressible {
+            continue;
+        }
+        let gz_path = gzip_path(&path);
+        if gz_path.exists() && gz_path.metadata()?.modified()? >= path.metadata()?.modified()? {
+            continue;
+        }
+        info!("Compressing {:?}", path);
+        let mut gz = GzEncoder::new(File::create(gz_path)?, Compression::best());
+        gz.write_all(&read_bytes(&path)?)?;
+        gz.finish()?;
+    }
+    Ok(())
+}
+
+/// Removes the copies `gzip_files` left in `dir` for files with one of the `extensions`, which
+/// would go stale once the files are built without compression
+pub fn remove_gzip_files(dir: &Path, extensions: &[&str]) -> OResult<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            remove_gzip_files(&path, extensions)?;
+            continue;
+        }
+        let is_gzip = path.extension().and_then(OsStr::to_str) == Some("gz");
+        let compressible = path
+            .with_extension("")
+            .extension()
+            .and_then(OsStr::to_str)
+            .map_or(false, |ext| extensions.contains(&ext));
+        if is_gzip && compressible {
+            info!("Removing {:?}", path);
+            fs::remove_file(path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Removes a built file along with the gzipped copy `gzip_files` may have left next to it
+pub fn remove_output(path: &Path) -> OResult<()> {
+    for path in &[path.to_owned(), gzip_path(path)] {
+        if path.exists() {
+            info!("Removing {:?}", path);
+            fs::remove_file(path)?;
+        }
+    }
     Ok(())
 }
 
+fn gzip_path(path: &Path) -> PathBuf {
+    let mut gz_name = path.to_owned().into_os_string();
+    gz_name.push(".gz");
+    PathBuf::from(gz_name)
+}
+
 pub fn write_file<P, B>(path: P, content: B) -> OResult<()>
 where
     P: AsRef<Path>,
     B: AsRef<[u8]>,
 {
-    let path = path.as_ref();
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent)?;
-    }
+    create_parent_dirs(&path)?;
     let mut br = BufWriter::new(File::create(path)?);
     br.write_all(content.as_ref())?;
     Ok(())
 }
 
-fn inspect_dom(dom: &mut RcDom) {
+pub fn create_parent_dirs<P: AsRef<Path>>(path: P) -> OResult<()> {
+    if let Some(parent) = path.as_ref().parent() {
+        fs::create_dir_all(parent)?;
+    }
+    Ok(())
+}
+
+fn inspect_dom(dom: &mut RcDom, options: &HtmlOptions) {
     let re = Regex::new("language-([a-z]+)").unwrap();
     let ss = SyntaxSet::load_defaults_newlines();
     let ts = ThemeSet::load_defaults();
-    inspect_node(&mut dom.document, &re, &ss, &ts);
+    inspect_node(&mut dom.document, &re, &ss, &ts, options);
 }
 
-fn inspect_node(node: &mut Handle, re: &Regex, ss: &SyntaxSet, ts: &ThemeSet) {
+fn inspect_node(
+    node: &mut Handle,
+    re: &Regex,
+    ss: &SyntaxSet,
+    ts: &ThemeSet,
+    options: &HtmlOptions,
+) {
     if let NodeData::Element {
         ref name,
         ref attrs,
         ..
     } = node.data
     {
-        if &name.local == "code" {
+        rewrite_links(name, attrs, options);
+        if options.highlight != Highlight::Off && &name.local == "code" {
             if let Some(attr) = attrs
                 .borrow()
                 .iter()
@@ -81,8 +249,19 @@ fn inspect_node(node: &mut Handle, re: &Regex, ss: &SyntaxSet, ts: &ThemeSet) {
                     let syntax = ss
                         .find_syntax_by_token(language_match.get(1).unwrap().as_str())
                         .unwrap_or(ss.find_syntax_plain_text());
-                    let mut h = HighlightLines::new(syntax, &ts.themes["base16-ocean.dark"]);
-                    let new = text_to_highlighted(&node.children.borrow(), h);
+                    let new = match options.highlight {
+                        Highlight::Classes => {
+                            text_to_highlighted(&node.children.borrow(), |code, parent| {
+                                classed_code(code, parent, syntax)
+                            })
+                        }
+                        _ => {
+                            let theme = &ts.themes["base16-ocean.dark"];
+                            text_to_highlighted(&node.children.borrow(), |code, parent| {
+                                highlight_code(code, parent, HighlightLines::new(syntax, theme))
+                            })
+                        }
+                    };
                     node.children.replace(new);
                     return;
                 }
@@ -91,15 +270,35 @@ fn inspect_node(node: &mut Handle, re: &Regex, ss: &SyntaxSet, ts: &ThemeSet) {
     }
 
     for child in node.children.borrow_mut().iter_mut() {
-        inspect_node(child, re, ss, ts)
+        inspect_node(child, re, ss, ts, options)
+    }
+}
+
+fn rewrite_links(name: &QualName, attrs: &RefCell<Vec<Attribute>>, options: &HtmlOptions) {
+    for attr in attrs.borrow_mut().iter_mut() {
+        let rewritten = match (&*name.local, &*attr.name.local) {
+            (_, "href") | (_, "src") => options.rewrite(&attr.value),
+            // redirect pages, `<meta http-equiv="refresh" content="0; url=/...">`
+            ("meta", "content") => attr.value.find("url=").and_then(|i| {
+                let (refresh, url) = attr.value.split_at(i + "url=".len());
+                options.rewrite(url).map(|url| format!("{}{}", refresh, url))
+            }),
+            _ => None,
+        };
+        if let Some(value) = rewritten {
+            attr.value = value.into();
+        }
     }
 }
 
-fn text_to_highlighted(children: &[Handle], h: HighlightLines) -> Vec<Handle> {
+fn text_to_highlighted<F>(children: &[Handle], highlight: F) -> Vec<Handle>
+where
+    F: FnOnce(&str, Handle) -> Vec<Handle>,
+{
     if let [node] = children {
         if let NodeData::Text { ref contents } = node.data {
             let parent = node.clone();
-            return highlight_code(&contents.borrow(), parent, h);
+            return highlight(&contents.borrow(), parent);
         }
     }
     error!("Can only highlight code blocks with single, text child node");
@@ -122,6 +321,38 @@ fn highlight_code(code: &str, parent: Handle, mut h: HighlightLines) -> Vec<Hand
     children
 }
 
+fn classed_code(code: &str, parent: Handle, syntax: &SyntaxDefinition) -> Vec<Handle> {
+    let mut children: Vec<Handle> = Vec::new();
+    let mut state = ParseState::new(syntax);
+    let mut stack = ScopeStack::new();
+    for line in code.lines() {
+        // the default syntaxes expect every line to end in a newline
+        let line = format!("{}\n", line);
+        let mut start = 0;
+        for (end, op) in state.parse_line(&line) {
+            push_classed(&mut children, &line[start..end], &stack, &parent);
+            start = end;
+            stack.apply(&op);
+        }
+        push_classed(&mut children, &line[start..], &stack, &parent);
+    }
+    children
+}
+
+/// Adds a token of code, in a span classed after its innermost scope unless it's whitespace
+fn push_classed(children: &mut Vec<Handle>, text: &str, stack: &ScopeStack, parent: &Handle) {
+    if text.is_empty() {
+        return;
+    }
+    match stack.as_slice().last() {
+        Some(scope) if !text.trim().is_empty() => {
+            let class = scope.build_string().replace('.', " ");
+            children.push(span_to_node("class", class, text, parent.clone()))
+        }
+        _ => children.push(text_to_node(text, parent.clone())),
+    }
+}
+
 fn text_to_node(text: &str, parent: Handle) -> Handle {
     Rc::new(Node {
         parent: Cell::new(Some(Rc::downgrade(&parent))),
@@ -133,14 +364,18 @@ fn text_to_node(text: &str, parent: Handle) -> Handle {
 }
 
 fn style_to_node(style: Style, text: &str, parent: Handle) -> Handle {
+    span_to_node("style", style_to_attr(style), text, parent)
+}
+
+fn span_to_node(attr: &str, value: String, text: &str, parent: Handle) -> Handle {
     let parent = Cell::new(Some(Rc::downgrade(&parent)));
-    let style = Attribute {
+    let attr = Attribute {
         name: QualName {
             prefix: None,
             ns: ns!(),
-            local: LocalName::from("style"),
+            local: LocalName::from(attr),
         },
-        value: style_to_attr(style).into(),
+        value: value.into(),
     };
     let data = NodeData::Element {
         name: QualName {
@@ -148,7 +383,7 @@ fn style_to_node(style: Style, text: &str, parent: Handle) -> Handle {
             ns: ns!(),
             local: LocalName::from("span"),
         },
-        attrs: RefCell::new(vec![style]),
+        attrs: RefCell::new(vec![attr]),
         template_contents: None,
         mathml_annotation_xml_integration_point: false,
     };
@@ -188,3 +423,38 @@ fn write_css_color(s: &mut String, c: Color) {
         write!(s, "#{:02x}{:02x}{:02x}", c.r, c.g, c.b).unwrap();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefixed_links() {
+        let prefixed = || Links::Prefixed("/site".to_owned());
+        assert_eq!(prefixed().rewrite("/blog/a.html"), Some("/site/blog/a.html".to_owned()));
+        assert_eq!(prefixed().rewrite("/"), Some("/site/".to_owned()));
+        assert_eq!(Links::Prefixed(String::new()).rewrite("/blog/a.html"), None);
+    }
+
+    #[test]
+    fn relative_links() {
+        assert_eq!(Links::Relative(0).rewrite("/styles.css"), Some("styles.css".to_owned()));
+        assert_eq!(
+            Links::Relative(2).rewrite("/styles.css"),
+            Some("../../styles.css".to_owned())
+        );
+        assert_eq!(Links::Relative(0).rewrite("/"), Some("index.html".to_owned()));
+        assert_eq!(
+            Links::Relative(1).rewrite("/travel/"),
+            Some("../travel/index.html".to_owned())
+        );
+    }
+
+    #[test]
+    fn other_links_left_alone() {
+        for url in &["blog/a.html", "#intro", "//cdn.example.com/a.js", "https://example.com/"] {
+            assert_eq!(Links::Prefixed("/site".to_owned()).rewrite(url), None);
+            assert_eq!(Links::Relative(1).rewrite(url), None);
+        }
+    }
+}